@@ -1,18 +1,20 @@
 use anyhow::{Context, Result};
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use chacha20poly1305::{
-    aead::{Aead, KeyInit},
+    aead::{Aead, KeyInit, OsRng},
     ChaCha20Poly1305, Nonce,
 };
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct EncryptedValue {
-    ciphertext: String,
-    nonce: String,
+/// A value sealed with ChaCha20Poly1305, base64-encoded for storage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedValue {
+    pub ciphertext: String,
+    pub nonce: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -59,7 +61,28 @@ fn derive_key_from_password(password: &str) -> [u8; 32] {
     key
 }
 
-fn decrypt_value(encrypted: &EncryptedValue, password: &str) -> Result<String> {
+/// Encrypt a value under a key derived from `password`, with a fresh random nonce.
+pub fn encrypt_value(value: &str, password: &str) -> Result<EncryptedValue> {
+    let key = derive_key_from_password(password);
+    let cipher = ChaCha20Poly1305::new_from_slice(&key)
+        .context("Failed to create cipher")?;
+
+    let mut nonce_bytes = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce_bytes);
+    let nonce = Nonce::from_slice(&nonce_bytes);
+
+    let ciphertext = cipher
+        .encrypt(nonce, value.as_bytes())
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt value"))?;
+
+    Ok(EncryptedValue {
+        ciphertext: BASE64.encode(&ciphertext),
+        nonce: BASE64.encode(nonce_bytes),
+    })
+}
+
+/// Decrypt a value sealed by [`encrypt_value`] with the same password.
+pub fn decrypt_value(encrypted: &EncryptedValue, password: &str) -> Result<String> {
     let key = derive_key_from_password(password);
     let cipher = ChaCha20Poly1305::new_from_slice(&key)
         .context("Failed to create cipher")?;
@@ -77,7 +77,7 @@ fn encrypt_value(value: &str, password: &str) -> Result<EncryptedValue> {
 
     Ok(EncryptedValue {
         ciphertext: BASE64.encode(&ciphertext),
-        nonce: BASE64.encode(&nonce_bytes),
+        nonce: BASE64.encode(nonce_bytes),
     })
 }
 
@@ -161,7 +161,7 @@ fn main() -> Result<()> {
     match matches.subcommand() {
         Some(("add", sub_matches)) => {
             let key = sub_matches.get_one::<String>("key").unwrap().clone();
-            let value = sub_matches.get_one::<String>("value").map(|s| s.clone());
+            let value = sub_matches.get_one::<String>("value").cloned();
             
             // Check for master password in environment variable first
             let env_password = std::env::var("MYRSS_MASTER_PASSWORD").ok();
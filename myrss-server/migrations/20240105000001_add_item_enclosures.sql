@@ -0,0 +1,15 @@
+-- Media attachments for feed items (RSS enclosures, JSON Feed attachments)
+CREATE TABLE item_enclosures (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    item_id UUID NOT NULL REFERENCES items(id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    mime_type VARCHAR(255),
+    length BIGINT,
+    title VARCHAR(500),
+    duration_seconds BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE(item_id, url)
+);
+
+-- Create indexes
+CREATE INDEX idx_item_enclosures_item ON item_enclosures(item_id);
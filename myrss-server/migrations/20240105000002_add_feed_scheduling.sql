@@ -0,0 +1,9 @@
+-- Per-feed polling schedule, adapted from how often new items appear
+ALTER TABLE feeds ADD COLUMN fetch_interval_secs INTEGER NOT NULL DEFAULT 3600;
+ALTER TABLE feeds ADD COLUMN next_fetch_at TIMESTAMPTZ;
+
+-- Per-subscription override of the polling interval (NULL = automatic)
+ALTER TABLE subscriptions ADD COLUMN refresh_interval_secs INTEGER;
+
+-- Create indexes
+CREATE INDEX idx_feeds_next_fetch_at ON feeds(next_fetch_at);
@@ -0,0 +1,3 @@
+-- Feeds paused after repeated failures or HTTP 410 Gone are skipped by the
+-- scheduler until a subscriber resumes them
+ALTER TABLE feeds ADD COLUMN paused_at TIMESTAMPTZ;
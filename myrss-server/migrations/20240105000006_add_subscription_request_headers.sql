@@ -0,0 +1,16 @@
+-- A feed fetched with a subscriber's own headers or cookies is private to
+-- that subscriber, so what their credentials unlock is never shown to anyone
+-- else subscribed to the same URL
+ALTER TABLE feeds ADD COLUMN owner_id UUID REFERENCES users(id) ON DELETE CASCADE;
+ALTER TABLE feeds DROP CONSTRAINT feeds_url_key;
+CREATE UNIQUE INDEX idx_feeds_public_url ON feeds(url) WHERE owner_id IS NULL;
+CREATE UNIQUE INDEX idx_feeds_owner_url ON feeds(owner_id, url) WHERE owner_id IS NOT NULL;
+
+-- Extra request headers (API keys, session cookies) sent when fetching a
+-- subscription's private feed
+CREATE TABLE subscription_request_headers (
+    subscription_id UUID NOT NULL REFERENCES subscriptions(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (subscription_id, name)
+);
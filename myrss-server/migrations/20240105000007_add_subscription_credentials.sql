@@ -0,0 +1,9 @@
+-- HTTP Basic credentials for a subscription's private feed, sealed with
+-- ChaCha20Poly1305 under the server's credentials key
+CREATE TABLE subscription_credentials (
+    subscription_id UUID PRIMARY KEY REFERENCES subscriptions(id) ON DELETE CASCADE,
+    ciphertext TEXT NOT NULL,
+    nonce TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
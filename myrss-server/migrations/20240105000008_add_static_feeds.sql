@@ -0,0 +1,28 @@
+-- Feeds added by pasting a document instead of a URL. They are never
+-- fetched; the document lives in static_feed_documents and the feed gets a
+-- synthetic `static:<id>` URL
+ALTER TABLE feeds ADD COLUMN kind VARCHAR(16) NOT NULL DEFAULT 'remote'
+    CHECK (kind IN ('remote', 'static'));
+
+CREATE TABLE static_feed_documents (
+    feed_id UUID PRIMARY KEY REFERENCES feeds(id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    uploaded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Earlier versions stored the pasted document itself as the feed URL
+INSERT INTO static_feed_documents (feed_id, content)
+SELECT id, url FROM feeds
+WHERE url NOT LIKE 'http://%' AND url NOT LIKE 'https://%';
+
+UPDATE feeds
+SET kind = 'static', url = 'static:' || id, next_fetch_at = NULL
+WHERE url NOT LIKE 'http://%' AND url NOT LIKE 'https://%';
+
+-- Give each converted feed to its subscriber when it has exactly one, so
+-- they can upload a new document for it
+UPDATE feeds f
+SET owner_id = (SELECT s.user_id FROM subscriptions s WHERE s.feed_id = f.id)
+WHERE f.kind = 'static'
+  AND f.owner_id IS NULL
+  AND (SELECT COUNT(*) FROM subscriptions s WHERE s.feed_id = f.id) = 1;
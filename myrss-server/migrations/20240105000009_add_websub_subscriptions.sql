@@ -0,0 +1,18 @@
+-- WebSub push subscriptions, one per feed whose hub we have subscribed to.
+-- `renew_at` is when to ask the hub again: shortly before the lease runs
+-- out, or after a while if a request was never verified or was denied
+CREATE TABLE websub_subscriptions (
+    feed_id UUID PRIMARY KEY REFERENCES feeds(id) ON DELETE CASCADE,
+    hub_url TEXT NOT NULL,
+    topic_url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    state VARCHAR(16) NOT NULL DEFAULT 'requested' CHECK (state IN ('requested', 'active', 'denied')),
+    lease_seconds INTEGER,
+    expires_at TIMESTAMPTZ,
+    renew_at TIMESTAMPTZ NOT NULL,
+    last_push_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_websub_subscriptions_renew_at ON websub_subscriptions(renew_at);
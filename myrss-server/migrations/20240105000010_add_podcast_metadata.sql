@@ -0,0 +1,14 @@
+-- iTunes podcast metadata for items
+ALTER TABLE items ADD COLUMN duration_seconds BIGINT;
+ALTER TABLE items ADD COLUMN episode INTEGER;
+ALTER TABLE items ADD COLUMN season INTEGER;
+ALTER TABLE items ADD COLUMN image_url TEXT;
+
+-- How far into an item's audio or video each user has got
+CREATE TABLE user_playback_positions (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    item_id UUID NOT NULL REFERENCES items(id) ON DELETE CASCADE,
+    position_seconds DOUBLE PRECISION NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, item_id)
+);
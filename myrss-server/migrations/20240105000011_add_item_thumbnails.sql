@@ -0,0 +1,2 @@
+-- Preview image for an item, from Media RSS or the first image in its HTML
+ALTER TABLE items ADD COLUMN thumbnail_url TEXT;
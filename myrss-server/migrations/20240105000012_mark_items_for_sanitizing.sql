@@ -0,0 +1,4 @@
+-- Items stored before ingest sanitized their HTML are cleaned once at
+-- startup; those stored from now on already are
+ALTER TABLE items ADD COLUMN needs_sanitizing BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE items ALTER COLUMN needs_sanitizing SET DEFAULT FALSE;
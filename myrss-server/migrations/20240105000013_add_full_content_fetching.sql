@@ -0,0 +1,15 @@
+-- Opt-in per subscription: download each item's link and keep the
+-- extracted article as the item's content
+ALTER TABLE subscriptions ADD COLUMN fetch_full_content BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- When the article behind an item was last downloaded, successfully or not
+ALTER TABLE items ADD COLUMN full_content_fetched_at TIMESTAMPTZ;
+
+-- Whether an extracted article replaced the item's content. Only then do
+-- feed updates leave the content alone; an item whose page had nothing to
+-- extract keeps following the feed
+ALTER TABLE items ADD COLUMN full_content_stored BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Failed downloads are retried with growing delays until they give up
+ALTER TABLE items ADD COLUMN full_content_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE items ADD COLUMN full_content_retry_at TIMESTAMPTZ;
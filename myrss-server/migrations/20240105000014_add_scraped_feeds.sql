@@ -0,0 +1,15 @@
+-- Feeds synthesized from a web page that has no feed of its own. The feed
+-- URL is the page; each refresh applies the page's CSS selectors to find
+-- items. Like static feeds they are private to whoever set them up
+ALTER TABLE feeds DROP CONSTRAINT feeds_kind_check;
+ALTER TABLE feeds ADD CONSTRAINT feeds_kind_check CHECK (kind IN ('remote', 'static', 'scraped'));
+
+CREATE TABLE scrape_rules (
+    feed_id UUID PRIMARY KEY REFERENCES feeds(id) ON DELETE CASCADE,
+    item_selector TEXT NOT NULL,
+    title_selector TEXT NOT NULL,
+    link_selector TEXT,
+    date_selector TEXT,
+    body_selector TEXT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
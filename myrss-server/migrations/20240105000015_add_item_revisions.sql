@@ -0,0 +1,15 @@
+-- Earlier versions of items, kept when a feed edits an entry's title,
+-- description or content. `created_at` is when the version was replaced
+CREATE TABLE item_revisions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    item_id UUID NOT NULL REFERENCES items(id) ON DELETE CASCADE,
+    title TEXT NOT NULL,
+    description TEXT,
+    content TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_item_revisions_item_id ON item_revisions(item_id);
+
+-- When the item was last edited by its feed, if ever
+ALTER TABLE items ADD COLUMN revised_at TIMESTAMPTZ;
@@ -0,0 +1,280 @@
+use crate::{
+    config::Config,
+    db,
+    discovery,
+    encoding,
+    http_client::HttpClient,
+    rss_fetcher,
+    sanitize,
+};
+use anyhow::Result;
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use scraper::{ElementRef, Html, Selector};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// How many articles to download per background pass; the rest wait for
+/// the next one.
+const MAX_ARTICLES_PER_PASS: i64 = 20;
+
+/// How long a background pass may spend downloading before the downloads
+/// still running are abandoned until the next pass.
+const PASS_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Delay before retrying a failed download, doubled after every failure,
+/// and the number of failures after which an item is given up on.
+const RETRY_SECS: i32 = 600;
+const MAX_ATTEMPTS: i32 = 5;
+
+/// Below this much text the best candidate is more likely a teaser or a
+/// paywall notice than the article, and the feed's own content is kept.
+const MIN_ARTICLE_CHARS: usize = 250;
+
+/// Page furniture that never belongs to an article.
+const REMOVED_TAGS: &[&str] = &[
+    "script", "style", "noscript", "template", "nav", "aside", "footer", "form", "button", "iframe", "svg", "canvas",
+];
+
+/// Class and id fragments marking an element as page furniture, unless
+/// one of `MAYBE_CONTENT` also appears.
+const UNLIKELY: &[&str] = &[
+    "banner", "breadcrumb", "comment", "community", "cookie", "disqus", "footer", "gdpr", "menu", "modal", "newsletter",
+    "pagination", "popup", "related", "share", "sidebar", "social", "sponsor", "subscribe",
+];
+const MAYBE_CONTENT: &[&str] = &["article", "body", "column", "content", "main"];
+
+const POSITIVE: &[&str] = &["article", "body", "content", "entry", "main", "page", "post", "story", "text"];
+const NEGATIVE: &[&str] = &["comment", "footer", "meta", "nav", "promo", "related", "share", "sidebar", "widget"];
+
+/// Download the articles behind the newest items of feeds where some
+/// subscriber asked for full content, and store what can be extracted in
+/// place of the feed's own content. Downloads share the feed fetch limits
+/// and the whole pass is cut off after `PASS_TIMEOUT`.
+///
+/// A page with nothing article-like on it is not tried again; a download
+/// that failed is retried with backoff.
+pub async fn fill_full_content(pool: &PgPool, client: &HttpClient, config: &Config) -> Result<()> {
+    let jobs = db::get_items_needing_full_content(pool, MAX_ARTICLES_PER_PASS)
+        .await?
+        .into_iter()
+        .map(|(item_id, link)| (link.clone(), (item_id, link)))
+        .collect();
+
+    let pass = rss_fetcher::run_limited(config, jobs, |(item_id, link)| {
+        let pool = pool.clone();
+        let client = client.clone();
+        async move {
+            if let Err(e) = fill_item(&pool, &client, item_id, &link).await {
+                log::error!("Failed to store full content for {}: {:#}", link, e);
+            }
+        }
+    });
+    if tokio::time::timeout(PASS_TIMEOUT, pass).await.is_err() {
+        log::warn!("Full content downloads still running after {:?}; the rest wait for the next pass", PASS_TIMEOUT);
+    }
+    Ok(())
+}
+
+async fn fill_item(pool: &PgPool, client: &HttpClient, item_id: Uuid, link: &str) -> Result<()> {
+    match fetch_article(client, link).await {
+        Ok(content) => db::set_item_full_content(pool, item_id, content.as_deref()).await,
+        Err(e) => {
+            log::warn!("Failed to fetch full content from {}: {:#}", link, e);
+            db::record_full_content_failure(pool, item_id, RETRY_SECS, MAX_ATTEMPTS).await
+        }
+    }
+}
+
+/// Download `url` and return its main content as sanitized HTML, or `None`
+/// when it is not an HTML page or nothing article-like was found.
+pub async fn fetch_article(client: &HttpClient, url: &str) -> Result<Option<String>> {
+    let (response, _) = client.get(url, &HeaderMap::new(), &HeaderMap::new()).await?;
+    let response = response.error_for_status()?;
+    let final_url = response.url().to_string();
+    let content_type = response.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let body = client.read_body(response).await?;
+
+    let content = encoding::decode_to_utf8(&body, content_type.as_deref());
+    if !discovery::is_html_document(content_type.as_deref(), &content) {
+        return Ok(None);
+    }
+    let html = String::from_utf8_lossy(&content);
+    Ok(extract(&html).map(|article| sanitize::clean_html(&article, Some(&final_url))))
+}
+
+/// Readability-style main content extraction: paragraphs score their
+/// parent and grandparent by length and commas, containers are nudged by
+/// tag and class names, and the best-scoring container, discounted by
+/// how much of its text is links, is taken as the article.
+pub fn extract(html: &str) -> Option<String> {
+    let mut document = Html::parse_document(html);
+    remove_furniture(&mut document);
+
+    let paragraphs = Selector::parse("p, pre, td").ok()?;
+    let mut scores = HashMap::new();
+    for paragraph in document.select(&paragraphs) {
+        let length = text_length(paragraph);
+        if length < 25 {
+            continue;
+        }
+        let commas = paragraph.text().map(|text| text.matches(',').count()).sum::<usize>();
+        let score = 1.0 + commas as f64 + (length / 100).min(3) as f64;
+
+        let ancestors = paragraph.ancestors().filter_map(ElementRef::wrap).take(2);
+        for (depth, ancestor) in ancestors.enumerate() {
+            let entry = scores.entry(ancestor.id()).or_insert_with(|| initial_score(ancestor));
+            *entry += score / (depth + 1) as f64;
+        }
+    }
+
+    let (best, _) = scores
+        .into_iter()
+        .filter_map(|(id, score)| {
+            let element = ElementRef::wrap(document.tree.get(id)?)?;
+            Some((element, score * (1.0 - link_density(element))))
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+
+    (text_length(best) >= MIN_ARTICLE_CHARS).then(|| best.inner_html())
+}
+
+fn remove_furniture(document: &mut Html) {
+    let furniture: Vec<_> = document
+        .tree
+        .nodes()
+        .filter_map(ElementRef::wrap)
+        .filter(|element| {
+            let name = element.value().name();
+            if matches!(name, "html" | "body" | "article" | "main") {
+                return false;
+            }
+            REMOVED_TAGS.contains(&name) || {
+                let names = class_and_id(*element);
+                contains_any(&names, UNLIKELY) && !contains_any(&names, MAYBE_CONTENT)
+            }
+        })
+        .map(|element| element.id())
+        .collect();
+
+    for id in furniture {
+        if let Some(mut node) = document.tree.get_mut(id) {
+            node.detach();
+        }
+    }
+}
+
+fn initial_score(element: ElementRef) -> f64 {
+    let tag_score = match element.value().name() {
+        "article" | "main" => 10.0,
+        "div" | "section" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "address" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    };
+    let names = class_and_id(element);
+    let mut class_score = 0.0;
+    if contains_any(&names, POSITIVE) {
+        class_score += 25.0;
+    }
+    if contains_any(&names, NEGATIVE) {
+        class_score -= 25.0;
+    }
+    tag_score + class_score
+}
+
+fn class_and_id(element: ElementRef) -> String {
+    let value = element.value();
+    format!("{} {}", value.attr("class").unwrap_or_default(), value.id().unwrap_or_default()).to_lowercase()
+}
+
+fn contains_any(names: &str, fragments: &[&str]) -> bool {
+    fragments.iter().any(|fragment| names.contains(fragment))
+}
+
+fn text_length(element: ElementRef) -> usize {
+    element.text().map(|text| text.trim().chars().count()).sum()
+}
+
+fn link_density(element: ElementRef) -> f64 {
+    let total = text_length(element);
+    if total == 0 {
+        return 0.0;
+    }
+    let Ok(links) = Selector::parse("a") else {
+        return 0.0;
+    };
+    let linked: usize = element.select(&links).map(text_length).sum();
+    linked as f64 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use actix_web::{web, App, HttpResponse, HttpServer};
+
+    const PAGE: &str = r#"<!DOCTYPE html>
+<html><head><title>Post</title><script>track()</script></head>
+<body>
+  <nav><a href="/">Home</a> <a href="/about">About</a></nav>
+  <div id="sidebar"><p>Popular posts, recent posts, archives, tags, and everything else you might want to click on instead.</p></div>
+  <div class="post-content">
+    <h1>A long article</h1>
+    <p>The first paragraph is long enough to count, and it has a couple of commas, which readability likes.</p>
+    <p>The second paragraph continues the story with <a href="/related">a relative link</a>, more words, and further detail.</p>
+    <p>The third paragraph wraps it up, having said everything worth saying, at considerably greater length than the rest.</p>
+    <img src="chart.png" onerror="steal()">
+    <div class="share-buttons"><p>Share this on every social network you can think of, please, we insist.</p></div>
+  </div>
+  <footer><p>Copyright, all rights reserved, no part may be reproduced without permission.</p></footer>
+</body></html>"#;
+
+    #[test]
+    fn extracts_main_content() {
+        let article = extract(PAGE).expect("no article found");
+        assert!(article.contains("The first paragraph"));
+        assert!(article.contains("The third paragraph"));
+        assert!(!article.contains("Popular posts"));
+        assert!(!article.contains("Share this"));
+        assert!(!article.contains("Copyright"));
+        assert!(!article.contains("Home"));
+
+        assert_eq!(extract("<html><body><p>Subscribe to read more.</p></body></html>"), None);
+    }
+
+    /// A fixture site serving the article page and a non-HTML download.
+    #[actix_web::test]
+    async fn fetches_from_fixture_server() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let server = HttpServer::new(|| {
+            App::new()
+                .route("/posts/1", web::get().to(|| async { HttpResponse::Ok().content_type("text/html; charset=utf-8").body(PAGE) }))
+                .route("/episode.mp3", web::get().to(|| async { HttpResponse::Ok().content_type("audio/mpeg").body(vec![0xFFu8; 64]) }))
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let client = HttpClient::new(&Config::for_tests()).unwrap();
+        let article = fetch_article(&client, &format!("{}/posts/1", base)).await.unwrap().expect("no article found");
+        assert!(article.contains("The second paragraph"));
+        assert!(article.contains(&format!(r#"<a href="{}/related" rel="noopener noreferrer">"#, base)));
+        assert!(article.contains(&format!(r#"<img src="{}/posts/chart.png">"#, base)));
+        assert!(!article.contains("steal"));
+
+        assert_eq!(fetch_article(&client, &format!("{}/episode.mp3", base)).await.unwrap(), None);
+        assert!(fetch_article(&client, &format!("{}/missing", base)).await.is_err());
+
+        handle.stop(true).await;
+    }
+}
@@ -1,6 +1,7 @@
 use anyhow::Result;
 use myrss_secrets::SecretsReader;
 use serde::Deserialize;
+use std::path::PathBuf;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
@@ -8,7 +9,46 @@ pub struct Config {
     pub host: String,
     pub port: u16,
     pub session_key: String,
+    /// Key that feed credentials are encrypted under in the database
+    pub credentials_key: String,
     pub auth_header: String,
+    /// Base URL the server is reachable at from the internet, e.g.
+    /// `https://rss.example.com`; WebSub hubs deliver to callbacks under it,
+    /// so push subscriptions are only made when it is set
+    pub public_url: Option<String>,
+    /// Seconds between background scheduler passes, each of which refreshes
+    /// feeds whose next fetch time has passed; 0 disables the scheduler
+    pub refresh_interval_secs: u64,
+    /// Maximum number of feeds fetched at once
+    pub fetch_concurrency: usize,
+    /// Maximum number of feeds fetched at once from any single host
+    pub fetch_per_host_concurrency: usize,
+    /// Consecutive failed fetches after which a feed is paused
+    pub max_fetch_failures: i32,
+    /// Seconds to wait for a connection to a feed's host
+    pub fetch_connect_timeout_secs: u64,
+    /// Seconds to wait for each read from a feed's host
+    pub fetch_read_timeout_secs: u64,
+    /// Seconds a whole request to a feed's host may take, body included
+    pub fetch_timeout_secs: u64,
+    /// Largest feed document, in bytes, that will be downloaded
+    pub max_feed_bytes: usize,
+    /// Allow fetching from private network addresses (10.0.0.0/8 and the like)
+    pub fetch_allow_private_networks: bool,
+    /// Hosts exempt from the address checks, e.g. `localhost` for a feed
+    /// generator running next to the server
+    pub fetch_allowed_hosts: Vec<String>,
+    /// `User-Agent` sent with every fetch
+    pub fetch_user_agent: String,
+    /// Upstream proxy for all fetches, e.g. `http://proxy.example:3128`. It
+    /// does its own DNS lookups, so it must block private addresses itself
+    pub fetch_proxy: Option<String>,
+    /// Directories `file://` feed URLs may point into
+    pub feed_directories: Vec<PathBuf>,
+    /// Directory whose `.xml`/`.json` files are ingested as feeds
+    pub watch_directory: Option<PathBuf>,
+    /// Seconds between scans of the watched directory
+    pub watch_interval_secs: u64,
 }
 
 impl Config {
@@ -18,7 +58,7 @@ impl Config {
         let master_password = std::env::var("MYRSS_MASTER_PASSWORD")
             .expect("MYRSS_MASTER_PASSWORD must be set");
 
-        let secrets = SecretsReader::new(&secrets_file, master_password)?;
+        let secrets = SecretsReader::new(&secrets_file, master_password.clone())?;
 
         Ok(Config {
             database_url: secrets.get_or_default(
@@ -30,8 +70,98 @@ impl Config {
                 .unwrap_or_else(|_| "8080".to_string())
                 .parse()?,
             session_key: secrets.get("session_key")?,
+            credentials_key: secrets.get_or_default("credentials_key", master_password),
             auth_header: std::env::var("MYRSS_AUTH_HEADER")
                 .unwrap_or_else(|_| "X-Authenticated-User".to_string()),
+            public_url: std::env::var("MYRSS_PUBLIC_URL")
+                .ok()
+                .map(|url| url.trim_end_matches('/').to_string())
+                .filter(|url| !url.is_empty()),
+            refresh_interval_secs: std::env::var("MYRSS_REFRESH_INTERVAL_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()?,
+            fetch_concurrency: std::env::var("MYRSS_FETCH_CONCURRENCY")
+                .unwrap_or_else(|_| "8".to_string())
+                .parse()?,
+            fetch_per_host_concurrency: std::env::var("MYRSS_FETCH_PER_HOST_CONCURRENCY")
+                .unwrap_or_else(|_| "2".to_string())
+                .parse()?,
+            max_fetch_failures: std::env::var("MYRSS_MAX_FETCH_FAILURES")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()?,
+            fetch_connect_timeout_secs: std::env::var("MYRSS_FETCH_CONNECT_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()?,
+            fetch_read_timeout_secs: std::env::var("MYRSS_FETCH_READ_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()?,
+            fetch_timeout_secs: std::env::var("MYRSS_FETCH_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "120".to_string())
+                .parse()?,
+            max_feed_bytes: std::env::var("MYRSS_MAX_FEED_BYTES")
+                .unwrap_or_else(|_| "10485760".to_string())
+                .parse()?,
+            fetch_allow_private_networks: std::env::var("MYRSS_FETCH_ALLOW_PRIVATE_NETWORKS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()?,
+            fetch_allowed_hosts: std::env::var("MYRSS_FETCH_ALLOWED_HOSTS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|host| !host.is_empty())
+                .map(str::to_string)
+                .collect(),
+            fetch_user_agent: std::env::var("MYRSS_FETCH_USER_AGENT")
+                .unwrap_or_else(|_| format!("myrss/{}", env!("CARGO_PKG_VERSION"))),
+            fetch_proxy: std::env::var("MYRSS_FETCH_PROXY")
+                .ok()
+                .filter(|proxy| !proxy.is_empty()),
+            feed_directories: std::env::var("MYRSS_FEED_DIRECTORIES")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .collect(),
+            watch_directory: std::env::var("MYRSS_WATCH_DIRECTORY")
+                .ok()
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+            watch_interval_secs: std::env::var("MYRSS_WATCH_INTERVAL_SECS")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()?,
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+impl Config {
+    /// Defaults for tests, with fetches from loopback allowed so they can
+    /// talk to stand-in servers.
+    pub fn for_tests() -> Self {
+        Config {
+            database_url: String::new(),
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            session_key: String::new(),
+            credentials_key: "test".to_string(),
+            auth_header: "X-Authenticated-User".to_string(),
+            public_url: None,
+            refresh_interval_secs: 0,
+            fetch_concurrency: 8,
+            fetch_per_host_concurrency: 2,
+            max_fetch_failures: 10,
+            fetch_connect_timeout_secs: 5,
+            fetch_read_timeout_secs: 5,
+            fetch_timeout_secs: 10,
+            max_feed_bytes: 1_048_576,
+            fetch_allow_private_networks: false,
+            fetch_allowed_hosts: vec!["127.0.0.1".to_string()],
+            fetch_user_agent: "myrss-test".to_string(),
+            fetch_proxy: None,
+            feed_directories: Vec::new(),
+            watch_directory: None,
+            watch_interval_secs: 5,
+        }
+    }
+}
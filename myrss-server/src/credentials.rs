@@ -0,0 +1,38 @@
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use myrss_secrets::EncryptedValue;
+
+/// Username and password for a feed behind HTTP Basic auth.
+///
+/// Deliberately not `Debug`, so the password can't end up in a log line.
+pub struct BasicCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl BasicCredentials {
+    /// Encrypt for storage. RFC 7617 forbids `:` in the user-id, so the
+    /// pair is sealed as a single `username:password` string.
+    pub fn seal(&self, key: &str) -> Result<EncryptedValue> {
+        if self.username.contains(':') {
+            anyhow::bail!("Usernames for HTTP Basic auth cannot contain ':'");
+        }
+        myrss_secrets::encrypt_value(&format!("{}:{}", self.username, self.password), key)
+    }
+
+    pub fn open(sealed: &EncryptedValue, key: &str) -> Result<Self> {
+        let plaintext = myrss_secrets::decrypt_value(sealed, key)
+            .context("Failed to decrypt feed credentials")?;
+        let (username, password) = plaintext.split_once(':')
+            .context("Malformed feed credentials")?;
+        Ok(Self {
+            username: username.to_string(),
+            password: password.to_string(),
+        })
+    }
+
+    /// Value of the `Authorization` header these credentials produce.
+    pub fn authorization(&self) -> String {
+        format!("Basic {}", BASE64.encode(format!("{}:{}", self.username, self.password)))
+    }
+}
@@ -0,0 +1,420 @@
+use time::format_description::well_known::{Rfc2822, Rfc3339};
+use time::{Date, Month, OffsetDateTime, Time, UtcOffset};
+
+/// Time zone abbreviations seen in the wild, with their offsets in minutes.
+/// Ambiguous names take their most common meaning in feeds (IST is India).
+const ZONE_ABBREVIATIONS: [(&str, i32); 44] = [
+    ("UT", 0),
+    ("UTC", 0),
+    ("GMT", 0),
+    ("Z", 0),
+    ("WET", 0),
+    ("WEST", 60),
+    ("BST", 60),
+    ("CET", 60),
+    ("CEST", 120),
+    ("MET", 60),
+    ("MEST", 120),
+    ("EET", 120),
+    ("EEST", 180),
+    ("MSK", 180),
+    ("IST", 330),
+    ("PKT", 300),
+    ("ICT", 420),
+    ("WIB", 420),
+    ("HKT", 480),
+    ("SGT", 480),
+    ("AWST", 480),
+    ("JST", 540),
+    ("KST", 540),
+    ("ACST", 570),
+    ("ACDT", 630),
+    ("AEST", 600),
+    ("AEDT", 660),
+    ("NZST", 720),
+    ("NZDT", 780),
+    ("NST", -210),
+    ("NDT", -150),
+    ("AST", -240),
+    ("ADT", -180),
+    ("EST", -300),
+    ("EDT", -240),
+    ("CST", -360),
+    ("CDT", -300),
+    ("MST", -420),
+    ("MDT", -360),
+    ("PST", -480),
+    ("PDT", -420),
+    ("AKST", -540),
+    ("AKDT", -480),
+    ("HST", -600),
+];
+
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// Parse a feed date as leniently as possible.
+///
+/// Handles RFC 2822 and RFC 3339 as well as the usual deviations from them:
+/// named time zones, missing seconds, two-digit years, wrong weekdays,
+/// date-only values, a space instead of `T`, and spelled-out dates such as
+/// "January 5th, 2024 10:00 AM PST". Dates without a zone are taken as UTC.
+pub fn parse_date(input: &str) -> Option<OffsetDateTime> {
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+
+    OffsetDateTime::parse(input, &Rfc3339).ok()
+        .or_else(|| OffsetDateTime::parse(input, &Rfc2822).ok())
+        .or_else(|| parse_iso_like(input))
+        .or_else(|| parse_textual(input))
+}
+
+/// `YYYY-MM-DD` or `YYYY/MM/DD`, optionally followed by a time and zone.
+fn parse_iso_like(input: &str) -> Option<OffsetDateTime> {
+    let date_part = input.get(..10)?;
+    let mut fields = date_part.split(['-', '/']);
+    let year = parse_number(fields.next()?, 4, 4)?;
+    let month = parse_number(fields.next()?, 1, 2)?;
+    let day = parse_number(fields.next()?, 1, 2)?;
+    let date = Date::from_calendar_date(year, Month::try_from(u8::try_from(month).ok()?).ok()?, u8::try_from(day).ok()?).ok()?;
+
+    let rest = &input[10..];
+    if rest.trim().is_empty() {
+        return Some(date.midnight().assume_utc());
+    }
+    let rest = rest.strip_prefix(['T', 't', ' '])?.trim_start();
+
+    let (time_str, zone_str) = split_time_and_zone(rest);
+    let time = parse_time(time_str)?;
+    let offset = match zone_str.trim() {
+        "" => UtcOffset::UTC,
+        zone => parse_zone(zone)?,
+    };
+    Some(date.with_time(time).assume_offset(offset))
+}
+
+/// Free-form dates built from words and numbers, in the spirit of RFC 2822
+/// but in any order: "Mon, 5 Jan 24 10:00 EST", "Jan 05 2024 10:00:00
+/// GMT+0100 (CET)", "5. January 2024".
+fn parse_textual(input: &str) -> Option<OffsetDateTime> {
+    // Drop parenthesized comments such as JavaScript's "(Central European Time)"
+    let mut cleaned = String::with_capacity(input.len());
+    let mut depth = 0usize;
+    for c in input.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => cleaned.push(' '),
+            _ if depth == 0 => cleaned.push(c),
+            _ => {}
+        }
+    }
+
+    let mut year: Option<i32> = None;
+    let mut month: Option<Month> = None;
+    let mut day: Option<u8> = None;
+    let mut time: Option<Time> = None;
+    let mut offset: Option<UtcOffset> = None;
+    let mut pm: Option<bool> = None;
+
+    for token in cleaned.split_whitespace() {
+        let lower = token.trim_end_matches('.').to_ascii_lowercase();
+
+        if lower.contains(':') && lower.starts_with(|c: char| c.is_ascii_digit()) {
+            let (time_str, zone_str) = split_time_and_zone(&lower);
+            time = Some(parse_time(time_str)?);
+            if !zone_str.is_empty() {
+                offset = Some(parse_zone(zone_str)?);
+            }
+        } else if matches!(lower.as_str(), "am" | "a.m") {
+            pm = Some(false);
+        } else if matches!(lower.as_str(), "pm" | "p.m") {
+            pm = Some(true);
+        } else if let Some(m) = month_from_name(&lower) {
+            month = Some(m);
+        } else if is_weekday_name(&lower) || matches!(lower.as_str(), "at" | "on" | "the" | "of") {
+            continue;
+        } else if lower.starts_with(|c: char| c.is_ascii_digit()) {
+            let digits = ["st", "nd", "rd", "th"]
+                .iter()
+                .find_map(|suffix| lower.strip_suffix(suffix))
+                .unwrap_or(&lower);
+            let value: i32 = digits.parse().ok()?;
+            if digits.len() == 4 {
+                year = Some(value);
+            } else if digits.len() <= 2 && day.is_none() {
+                day = Some(u8::try_from(value).ok()?);
+            } else if digits.len() == 2 && year.is_none() {
+                year = Some(expand_two_digit_year(value));
+            } else {
+                return None;
+            }
+        } else {
+            offset = Some(parse_zone(token)?);
+        }
+    }
+
+    let mut time = time.unwrap_or(Time::MIDNIGHT);
+    if let Some(pm) = pm {
+        let hour = match (time.hour(), pm) {
+            (12, false) => 0,
+            (h, true) if h < 12 => h + 12,
+            (h, _) => h,
+        };
+        time = time.replace_hour(hour).ok()?;
+    }
+
+    let date = Date::from_calendar_date(year?, month?, day?).ok()?;
+    Some(date.with_time(time).assume_offset(offset.unwrap_or(UtcOffset::UTC)))
+}
+
+/// Split `10:00:00+01:00` or `10:00Z` into the time and whatever zone
+/// designator is stuck to its end.
+fn split_time_and_zone(input: &str) -> (&str, &str) {
+    let end = input
+        .find(|c: char| !(c.is_ascii_digit() || c == ':' || c == '.' || c == ','))
+        .unwrap_or(input.len());
+    let (time, zone) = input.split_at(end);
+    (time, zone.trim())
+}
+
+/// `hh:mm`, `hh:mm:ss` or `hh:mm:ss.fraction`.
+fn parse_time(input: &str) -> Option<Time> {
+    let mut parts = input.split(':');
+    let hour = parse_number(parts.next()?, 1, 2)?;
+    let minute = parse_number(parts.next()?, 2, 2)?;
+    let (second, nanosecond) = match parts.next() {
+        Some(seconds) => {
+            let (whole, fraction) = seconds.split_once(['.', ',']).unwrap_or((seconds, ""));
+            let second = parse_number(whole, 2, 2)?;
+            let fraction = &fraction[..fraction.len().min(9)];
+            let nanosecond = if fraction.is_empty() {
+                0
+            } else {
+                parse_number(fraction, 1, 9)? * 10_i32.pow(9 - fraction.len() as u32)
+            };
+            (second, nanosecond)
+        }
+        None => (0, 0),
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    // Leap seconds are clamped rather than rejected
+    Time::from_hms_nano(
+        u8::try_from(hour).ok()?,
+        u8::try_from(minute).ok()?,
+        u8::try_from(second.min(59)).ok()?,
+        u32::try_from(nanosecond).ok()?,
+    ).ok()
+}
+
+/// A zone designator: `Z`, `+0100`, `-05:00`, `+2`, `GMT+0100`, `UTC-5` or a
+/// known abbreviation.
+fn parse_zone(input: &str) -> Option<UtcOffset> {
+    let upper = input.trim().to_ascii_uppercase();
+
+    if let Some(rest) = upper.strip_prefix('+') {
+        return parse_numeric_offset(rest, 1);
+    }
+    if let Some(rest) = upper.strip_prefix('-') {
+        return parse_numeric_offset(rest, -1);
+    }
+    for prefix in ["GMT", "UTC", "UT"] {
+        if let Some(rest) = upper.strip_prefix(prefix) {
+            if rest.starts_with(['+', '-']) {
+                return parse_zone(rest);
+            }
+        }
+    }
+
+    ZONE_ABBREVIATIONS
+        .iter()
+        .find(|(name, _)| *name == upper)
+        .and_then(|(_, minutes)| UtcOffset::from_whole_seconds(minutes * 60).ok())
+}
+
+fn parse_numeric_offset(input: &str, sign: i32) -> Option<UtcOffset> {
+    let digits: String = input.chars().filter(|c| *c != ':').collect();
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = match digits.len() {
+        1 | 2 => (digits.parse::<i32>().ok()?, 0),
+        3 | 4 => {
+            let split = digits.len() - 2;
+            (digits[..split].parse().ok()?, digits[split..].parse().ok()?)
+        }
+        _ => return None,
+    };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    UtcOffset::from_whole_seconds(sign * (hours * 3600 + minutes * 60)).ok()
+}
+
+fn parse_number(input: &str, min_len: usize, max_len: usize) -> Option<i32> {
+    if input.len() < min_len || input.len() > max_len || !input.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    input.parse().ok()
+}
+
+/// "Jan", "January", "Sept" and the like.
+fn month_from_name(name: &str) -> Option<Month> {
+    if name.len() < 3 || !name.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let index = MONTHS.iter().position(|month| name.starts_with(month))?;
+    Month::try_from(index as u8 + 1).ok()
+}
+
+fn is_weekday_name(name: &str) -> bool {
+    name.len() >= 3 && name.chars().all(|c| c.is_ascii_alphabetic()) && WEEKDAYS.iter().any(|day| name.starts_with(day))
+}
+
+/// RFC 2822's rule for obsolete two-digit years: 00-49 is 2000-2049,
+/// 50-99 is 1950-1999.
+fn expand_two_digit_year(year: i32) -> i32 {
+    if year < 50 {
+        2000 + year
+    } else {
+        1900 + year
+    }
+}
+
+/// Parse an `<itunes:duration>`: plain seconds, `MM:SS` or `HH:MM:SS`,
+/// with fractional seconds allowed and dropped.
+pub fn parse_duration(input: &str) -> Option<i64> {
+    let mut seconds = 0i64;
+    let parts: Vec<&str> = input.trim().split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    for (i, part) in parts.iter().enumerate() {
+        let value: f64 = part.trim().parse().ok()?;
+        // Only the seconds may have a fraction
+        if !value.is_finite() || value < 0.0 || (i + 1 < parts.len() && value.fract() != 0.0) {
+            return None;
+        }
+        seconds = seconds.checked_mul(60)?.checked_add(value as i64)?;
+    }
+    Some(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    fn parsed(input: &str) -> OffsetDateTime {
+        parse_date(input).unwrap_or_else(|| panic!("failed to parse {:?}", input))
+    }
+
+    #[test]
+    fn rfc_2822() {
+        assert_eq!(parsed("Fri, 05 Jan 2024 10:00:00 +0000"), datetime!(2024-01-05 10:00:00 UTC));
+        assert_eq!(parsed("Fri, 05 Jan 2024 10:00:00 GMT"), datetime!(2024-01-05 10:00:00 UTC));
+        assert_eq!(parsed("Fri, 5 Jan 2024 10:00:00 -0500"), datetime!(2024-01-05 15:00:00 UTC));
+    }
+
+    #[test]
+    fn rfc_3339() {
+        assert_eq!(parsed("2024-01-05T10:00:00Z"), datetime!(2024-01-05 10:00:00 UTC));
+        assert_eq!(parsed("2024-01-05T10:00:00.123+02:00"), datetime!(2024-01-05 08:00:00.123 UTC));
+    }
+
+    #[test]
+    fn named_time_zones() {
+        assert_eq!(parsed("Mon, 5 Jan 2024 10:00:00 EST"), datetime!(2024-01-05 15:00:00 UTC));
+        assert_eq!(parsed("Tue, 09 Jul 2024 08:30:00 PDT"), datetime!(2024-07-09 15:30:00 UTC));
+        assert_eq!(parsed("Wed, 10 Jul 2024 12:00:00 CEST"), datetime!(2024-07-10 10:00:00 UTC));
+        assert_eq!(parsed("Thu, 11 Jul 2024 18:00:00 IST"), datetime!(2024-07-11 12:30:00 UTC));
+        assert_eq!(parsed("Fri, 12 Jul 2024 09:00:00 UT"), datetime!(2024-07-12 09:00:00 UTC));
+    }
+
+    #[test]
+    fn wrong_weekday_is_ignored() {
+        // 5 January 2024 was a Friday
+        assert_eq!(parsed("Mon, 05 Jan 2024 10:00:00 +0000"), datetime!(2024-01-05 10:00:00 UTC));
+    }
+
+    #[test]
+    fn missing_seconds() {
+        assert_eq!(parsed("Fri, 05 Jan 2024 10:00 +0000"), datetime!(2024-01-05 10:00:00 UTC));
+        assert_eq!(parsed("2024-01-05T10:00+01:00"), datetime!(2024-01-05 09:00:00 UTC));
+        assert_eq!(parsed("2024-01-05T10:00Z"), datetime!(2024-01-05 10:00:00 UTC));
+    }
+
+    #[test]
+    fn two_digit_years() {
+        assert_eq!(parsed("Fri, 05 Jan 24 10:00:00 GMT"), datetime!(2024-01-05 10:00:00 UTC));
+        assert_eq!(parsed("Sun, 26 Dec 99 23:59:59 GMT"), datetime!(1999-12-26 23:59:59 UTC));
+    }
+
+    #[test]
+    fn missing_zone_is_utc() {
+        assert_eq!(parsed("Fri, 05 Jan 2024 10:00:00"), datetime!(2024-01-05 10:00:00 UTC));
+        assert_eq!(parsed("2024-01-05 10:00:00"), datetime!(2024-01-05 10:00:00 UTC));
+        assert_eq!(parsed("2024-01-05T10:00:00"), datetime!(2024-01-05 10:00:00 UTC));
+    }
+
+    #[test]
+    fn date_only() {
+        assert_eq!(parsed("2024-01-05"), datetime!(2024-01-05 00:00:00 UTC));
+        assert_eq!(parsed("2024/01/05"), datetime!(2024-01-05 00:00:00 UTC));
+        assert_eq!(parsed("5 January 2024"), datetime!(2024-01-05 00:00:00 UTC));
+    }
+
+    #[test]
+    fn spelled_out_dates() {
+        assert_eq!(parsed("January 5, 2024"), datetime!(2024-01-05 00:00:00 UTC));
+        assert_eq!(parsed("January 5th, 2024 10:00 AM PST"), datetime!(2024-01-05 18:00:00 UTC));
+        assert_eq!(parsed("Sept 3 2024 12:15 am"), datetime!(2024-09-03 00:15:00 UTC));
+        assert_eq!(parsed("Friday, January 5, 2024 at 3:45 PM"), datetime!(2024-01-05 15:45:00 UTC));
+    }
+
+    #[test]
+    fn numeric_zone_variants() {
+        assert_eq!(parsed("Fri, 05 Jan 2024 10:00:00 +01:00"), datetime!(2024-01-05 09:00:00 UTC));
+        assert_eq!(parsed("Fri, 05 Jan 2024 10:00:00 GMT+2"), datetime!(2024-01-05 08:00:00 UTC));
+        assert_eq!(parsed("2024-01-05 10:00:00 -0530"), datetime!(2024-01-05 15:30:00 UTC));
+    }
+
+    #[test]
+    fn javascript_date_string() {
+        assert_eq!(
+            parsed("Fri Jan 05 2024 10:00:00 GMT+0100 (Central European Standard Time)"),
+            datetime!(2024-01-05 09:00:00 UTC)
+        );
+    }
+
+    #[test]
+    fn surrounding_whitespace() {
+        assert_eq!(parsed("\n    Fri, 05 Jan 2024 10:00:00 GMT\n  "), datetime!(2024-01-05 10:00:00 UTC));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(parse_date(""), None);
+        assert_eq!(parse_date("yesterday"), None);
+        assert_eq!(parse_date("Fri, 05 Jan 2024 10:00:00 XYZ"), None);
+        assert_eq!(parse_date("2024-13-05"), None);
+        assert_eq!(parse_date("31 Feb 2024"), None);
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("3600"), Some(3600));
+        assert_eq!(parse_duration("42:10"), Some(2530));
+        assert_eq!(parse_duration("1:02:03"), Some(3723));
+        assert_eq!(parse_duration(" 01:00:00.500 "), Some(3600));
+        assert_eq!(parse_duration("90.7"), Some(90));
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("1:2:3:4"), None);
+        assert_eq!(parse_duration("1.5:00"), None);
+        assert_eq!(parse_duration("soon"), None);
+    }
+}
@@ -1,15 +1,20 @@
-use crate::models::*;
+use crate::{models::*, revisions};
 use anyhow::Result;
-use sqlx::{PgPool, Row};
+use myrss_secrets::EncryptedValue;
+use sqlx::{PgPool, Postgres, Row, Transaction};
 use uuid::Uuid;
 
+/// The `feeds` columns read into a `Feed`, for `SELECT` and `RETURNING`
+const FEED_COLUMNS: &str = "id, url, title, description, last_fetched, fetch_interval_secs, next_fetch_at, etag, \
+    last_modified, last_error, last_status, consecutive_failures, last_success_at, paused_at, kind, created_at, updated_at";
+
 // User management functions
 pub async fn create_user(pool: &PgPool, username: &str, email: &str, password_hash: &str) -> Result<User> {
     let user = sqlx::query_as::<_, User>(
         r#"
         INSERT INTO users (username, email, password_hash)
         VALUES ($1, $2, $3)
-        RETURNING id, username, email, password_hash, created_at, updated_at
+        RETURNING id, username, email, created_at, updated_at
         "#
     )
     .bind(username)
@@ -24,7 +29,7 @@ pub async fn create_user(pool: &PgPool, username: &str, email: &str, password_ha
 pub async fn authenticate_user(pool: &PgPool, username: &str, password_hash: &str) -> Result<User> {
     let user = sqlx::query_as::<_, User>(
         r#"
-        SELECT id, username, email, password_hash, created_at, updated_at 
+        SELECT id, username, email, created_at, updated_at 
         FROM users 
         WHERE username = $1 AND password_hash = $2
         "#
@@ -40,7 +45,7 @@ pub async fn authenticate_user(pool: &PgPool, username: &str, password_hash: &st
 pub async fn get_user_by_id(pool: &PgPool, user_id: Uuid) -> Result<User> {
     let user = sqlx::query_as::<_, User>(
         r#"
-        SELECT id, username, email, password_hash, created_at, updated_at 
+        SELECT id, username, email, created_at, updated_at 
         FROM users 
         WHERE id = $1
         "#
@@ -52,27 +57,16 @@ pub async fn get_user_by_id(pool: &PgPool, user_id: Uuid) -> Result<User> {
     Ok(user)
 }
 
-pub async fn get_user_by_username(pool: &PgPool, username: &str) -> Result<Option<User>> {
-    let user = sqlx::query_as::<_, User>(
-        r#"SELECT id, username, email, password_hash, created_at, updated_at FROM users WHERE username = $1"#
-    )
-    .bind(username)
-    .fetch_optional(pool)
-    .await?;
-    
-    Ok(user)
-}
-
 // Feed management functions
 pub async fn create_or_get_feed(pool: &PgPool, url: &str) -> Result<Feed> {
-    let feed = sqlx::query_as::<_, Feed>(
+    let feed = sqlx::query_as::<_, Feed>(&format!(
         r#"
         INSERT INTO feeds (url)
         VALUES ($1)
-        ON CONFLICT (url) DO UPDATE SET url = EXCLUDED.url
-        RETURNING id, url, title, description, last_fetched, created_at, updated_at
+        ON CONFLICT (url) WHERE owner_id IS NULL DO UPDATE SET url = EXCLUDED.url
+        RETURNING {FEED_COLUMNS}
         "#
-    )
+    ))
     .bind(url)
     .fetch_one(pool)
     .await?;
@@ -80,6 +74,194 @@ pub async fn create_or_get_feed(pool: &PgPool, url: &str) -> Result<Feed> {
     Ok(feed)
 }
 
+pub async fn get_feed(pool: &PgPool, feed_id: Uuid) -> Result<Feed> {
+    let feed = sqlx::query_as::<_, Feed>(&format!(
+        r#"
+        SELECT {FEED_COLUMNS}
+        FROM feeds
+        WHERE id = $1
+        "#
+    ))
+    .bind(feed_id)
+    .fetch_one(pool)
+    .await?;
+    
+    Ok(feed)
+}
+
+pub async fn update_feed_cache_validators(
+    pool: &PgPool,
+    feed_id: Uuid,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE feeds 
+        SET etag = $2, last_modified = $3
+        WHERE id = $1
+        "#
+    )
+    .bind(feed_id)
+    .bind(etag)
+    .bind(last_modified)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+pub async fn get_user_feeds(pool: &PgPool, user_id: Uuid) -> Result<Vec<Feed>> {
+    let feeds = sqlx::query_as::<_, Feed>(&format!(
+        r#"
+        SELECT {FEED_COLUMNS}
+        FROM feeds
+        WHERE id IN (SELECT feed_id FROM subscriptions WHERE user_id = $1)
+          AND paused_at IS NULL AND kind IN ('remote', 'scraped')
+        "#
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(feeds)
+}
+
+pub async fn get_due_feeds(pool: &PgPool) -> Result<Vec<Feed>> {
+    let feeds = sqlx::query_as::<_, Feed>(&format!(
+        r#"
+        SELECT {FEED_COLUMNS}
+        FROM feeds
+        WHERE kind IN ('remote', 'scraped') AND paused_at IS NULL AND (next_fetch_at IS NULL OR next_fetch_at <= NOW())
+        ORDER BY next_fetch_at ASC NULLS FIRST
+        "#
+    ))
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(feeds)
+}
+
+/// Public feeds whose URL starts with `prefix`, e.g. those ingested from the
+/// watched directory.
+pub async fn get_feeds_with_url_prefix(pool: &PgPool, prefix: &str) -> Result<Vec<Feed>> {
+    let feeds = sqlx::query_as::<_, Feed>(&format!(
+        r#"
+        SELECT {FEED_COLUMNS}
+        FROM feeds
+        WHERE owner_id IS NULL AND kind = 'remote' AND starts_with(url, $1)
+        ORDER BY title, url
+        "#
+    ))
+    .bind(prefix)
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(feeds)
+}
+
+/// The shortest polling interval any subscriber has asked for, if any.
+pub async fn get_feed_interval_override(pool: &PgPool, feed_id: Uuid) -> Result<Option<i32>> {
+    let interval: Option<i32> = sqlx::query_scalar(
+        r#"
+        SELECT MIN(refresh_interval_secs)
+        FROM subscriptions
+        WHERE feed_id = $1
+        "#
+    )
+    .bind(feed_id)
+    .fetch_one(pool)
+    .await?;
+    
+    Ok(interval)
+}
+
+pub async fn update_feed_schedule(
+    pool: &PgPool,
+    feed_id: Uuid,
+    fetch_interval_secs: i32,
+    next_fetch_at: time::OffsetDateTime,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE feeds 
+        SET fetch_interval_secs = $2, next_fetch_at = $3
+        WHERE id = $1
+        "#
+    )
+    .bind(feed_id)
+    .bind(fetch_interval_secs)
+    .bind(next_fetch_at)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+pub async fn record_fetch_success(pool: &PgPool, feed_id: Uuid, status: Option<u16>) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE feeds 
+        SET last_error = NULL, last_status = $2, consecutive_failures = 0, last_success_at = NOW()
+        WHERE id = $1
+        "#
+    )
+    .bind(feed_id)
+    .bind(status.map(i32::from))
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+/// Record a failed fetch, schedule the retry and optionally pause the feed.
+pub async fn record_fetch_failure(
+    pool: &PgPool,
+    feed_id: Uuid,
+    error: &str,
+    status: Option<u16>,
+    next_fetch_at: time::OffsetDateTime,
+    pause: bool,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE feeds 
+        SET last_error = $2,
+            last_status = $3,
+            consecutive_failures = consecutive_failures + 1,
+            next_fetch_at = $4,
+            paused_at = CASE WHEN $5 THEN NOW() ELSE paused_at END
+        WHERE id = $1
+        "#
+    )
+    .bind(feed_id)
+    .bind(error)
+    .bind(status.map(i32::from))
+    .bind(next_fetch_at)
+    .bind(pause)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+/// Un-pause the feed behind a subscription and fetch it on the next pass.
+pub async fn resume_subscription_feed(pool: &PgPool, subscription_id: Uuid) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE feeds f
+        SET paused_at = NULL, consecutive_failures = 0, next_fetch_at = NOW()
+        FROM subscriptions s
+        WHERE s.id = $1 AND s.feed_id = f.id
+        "#
+    )
+    .bind(subscription_id)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
 pub async fn update_feed_metadata(
     pool: &PgPool,
     feed_id: Uuid,
@@ -102,6 +284,278 @@ pub async fn update_feed_metadata(
     Ok(())
 }
 
+/// Point a feed at the URL it has permanently moved to. If another feed row
+/// already has that URL, the two are merged into it: subscriptions, labels,
+/// items and read state carry over and the old row is deleted. Returns the id
+/// of the feed that now owns the URL. A private feed only ever merges with
+/// another private feed of the same owner.
+pub async fn move_feed_url(pool: &PgPool, feed_id: Uuid, new_url: &str) -> Result<Uuid> {
+    let mut tx = pool.begin().await?;
+    
+    let existing: Option<Uuid> = sqlx::query_scalar(
+        r#"
+        SELECT id FROM feeds
+        WHERE url = $1 AND id <> $2
+          AND owner_id IS NOT DISTINCT FROM (SELECT owner_id FROM feeds WHERE id = $2)
+        "#
+    )
+    .bind(new_url)
+    .bind(feed_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    
+    let Some(target_id) = existing else {
+        sqlx::query(
+            r#"
+            UPDATE feeds
+            SET url = $2, updated_at = NOW()
+            WHERE id = $1
+            "#
+        )
+        .bind(feed_id)
+        .bind(new_url)
+        .execute(&mut *tx)
+        .await?;
+        
+        tx.commit().await?;
+        return Ok(feed_id);
+    };
+    
+    // Users subscribed to both keep their existing subscription, picking up
+    // the labels from the one about to disappear
+    sqlx::query(
+        r#"
+        INSERT INTO subscription_labels (subscription_id, label_id)
+        SELECT keep.id, sl.label_id
+        FROM subscriptions old
+        JOIN subscriptions keep ON keep.user_id = old.user_id AND keep.feed_id = $2
+        JOIN subscription_labels sl ON sl.subscription_id = old.id
+        WHERE old.feed_id = $1
+        ON CONFLICT DO NOTHING
+        "#
+    )
+    .bind(feed_id)
+    .bind(target_id)
+    .execute(&mut *tx)
+    .await?;
+    
+    sqlx::query(
+        r#"
+        UPDATE subscriptions
+        SET feed_id = $2
+        WHERE feed_id = $1
+          AND user_id NOT IN (SELECT user_id FROM subscriptions WHERE feed_id = $2)
+        "#
+    )
+    .bind(feed_id)
+    .bind(target_id)
+    .execute(&mut *tx)
+    .await?;
+    
+    // Read state on items both feeds carry follows the surviving copy
+    sqlx::query(
+        r#"
+        INSERT INTO user_read_items (user_id, item_id, is_read, read_at)
+        SELECT ur.user_id, keep.id, ur.is_read, ur.read_at
+        FROM user_read_items ur
+        JOIN items old ON old.id = ur.item_id
+        JOIN items keep ON keep.feed_id = $2 AND keep.guid = old.guid
+        WHERE old.feed_id = $1
+        ON CONFLICT (user_id, item_id) DO NOTHING
+        "#
+    )
+    .bind(feed_id)
+    .bind(target_id)
+    .execute(&mut *tx)
+    .await?;
+    
+    sqlx::query(
+        r#"
+        UPDATE items
+        SET feed_id = $2
+        WHERE feed_id = $1
+          AND guid NOT IN (SELECT guid FROM items WHERE feed_id = $2)
+        "#
+    )
+    .bind(feed_id)
+    .bind(target_id)
+    .execute(&mut *tx)
+    .await?;
+    
+    sqlx::query(
+        r#"
+        DELETE FROM feeds WHERE id = $1
+        "#
+    )
+    .bind(feed_id)
+    .execute(&mut *tx)
+    .await?;
+    
+    tx.commit().await?;
+    Ok(target_id)
+}
+
+/// Create a static feed, private to `owner_id`, from a pasted document.
+pub async fn create_static_feed(pool: &PgPool, owner_id: Uuid, content: &str) -> Result<Feed> {
+    let mut tx = pool.begin().await?;
+    
+    let feed = sqlx::query_as::<_, Feed>(&format!(
+        r#"
+        INSERT INTO feeds (url, owner_id, kind)
+        VALUES ('static:' || gen_random_uuid(), $1, 'static')
+        RETURNING {FEED_COLUMNS}
+        "#
+    ))
+    .bind(owner_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    
+    sqlx::query(
+        r#"
+        INSERT INTO static_feed_documents (feed_id, content)
+        VALUES ($1, $2)
+        "#
+    )
+    .bind(feed.id)
+    .bind(content)
+    .execute(&mut *tx)
+    .await?;
+    
+    tx.commit().await?;
+    Ok(feed)
+}
+
+/// The static feed behind a subscription, or `None` if it is fetched
+/// from a URL.
+pub async fn get_subscription_static_feed(pool: &PgPool, subscription_id: Uuid) -> Result<Option<Uuid>> {
+    let feed_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        SELECT f.id
+        FROM subscriptions s
+        JOIN feeds f ON f.id = s.feed_id
+        WHERE s.id = $1 AND f.kind = 'static'
+        "#
+    )
+    .bind(subscription_id)
+    .fetch_optional(pool)
+    .await?;
+    
+    Ok(feed_id)
+}
+
+/// Replace the stored document of a static feed with a newer upload.
+pub async fn update_static_feed_document(pool: &PgPool, feed_id: Uuid, content: &str) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE static_feed_documents
+        SET content = $2, uploaded_at = NOW()
+        WHERE feed_id = $1
+        "#
+    )
+    .bind(feed_id)
+    .bind(content)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+/// Create a feed scraped from the web page at `url`, private to `owner_id`.
+/// Returns `None` if they already have a feed for that page.
+pub async fn create_scraped_feed(pool: &PgPool, owner_id: Uuid, url: &str, rules: &ScrapeRules) -> Result<Option<Feed>> {
+    let mut tx = pool.begin().await?;
+    
+    let feed = sqlx::query_as::<_, Feed>(&format!(
+        r#"
+        INSERT INTO feeds (url, owner_id, kind)
+        VALUES ($1, $2, 'scraped')
+        ON CONFLICT (owner_id, url) WHERE owner_id IS NOT NULL DO NOTHING
+        RETURNING {FEED_COLUMNS}
+        "#
+    ))
+    .bind(url)
+    .bind(owner_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    
+    let Some(feed) = feed else {
+        return Ok(None);
+    };
+    
+    sqlx::query(
+        r#"
+        INSERT INTO scrape_rules (feed_id, item_selector, title_selector, link_selector, date_selector, body_selector)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#
+    )
+    .bind(feed.id)
+    .bind(&rules.item_selector)
+    .bind(&rules.title_selector)
+    .bind(&rules.link_selector)
+    .bind(&rules.date_selector)
+    .bind(&rules.body_selector)
+    .execute(&mut *tx)
+    .await?;
+    
+    tx.commit().await?;
+    Ok(Some(feed))
+}
+
+pub async fn get_scrape_rules(pool: &PgPool, feed_id: Uuid) -> Result<Option<ScrapeRules>> {
+    let rules = sqlx::query_as::<_, ScrapeRules>(
+        r#"
+        SELECT item_selector, title_selector, link_selector, date_selector, body_selector
+        FROM scrape_rules
+        WHERE feed_id = $1
+        "#
+    )
+    .bind(feed_id)
+    .fetch_optional(pool)
+    .await?;
+    
+    Ok(rules)
+}
+
+/// Replace the selectors of the scraped feed behind a subscription. Returns
+/// the feed, or `None` if the subscription is not to a scraped feed.
+pub async fn update_subscription_scrape_rules(pool: &PgPool, subscription_id: Uuid, rules: &ScrapeRules) -> Result<Option<Uuid>> {
+    let mut tx = pool.begin().await?;
+    
+    let feed_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        UPDATE scrape_rules r
+        SET item_selector = $2, title_selector = $3, link_selector = $4, date_selector = $5, body_selector = $6,
+            updated_at = NOW()
+        FROM subscriptions s
+        WHERE s.id = $1 AND r.feed_id = s.feed_id
+        RETURNING r.feed_id
+        "#
+    )
+    .bind(subscription_id)
+    .bind(&rules.item_selector)
+    .bind(&rules.title_selector)
+    .bind(&rules.link_selector)
+    .bind(&rules.date_selector)
+    .bind(&rules.body_selector)
+    .fetch_optional(&mut *tx)
+    .await?;
+    
+    // The page may not have changed, but what is read from it has
+    sqlx::query(
+        r#"
+        UPDATE feeds
+        SET etag = NULL, last_modified = NULL
+        WHERE id = $1
+        "#
+    )
+    .bind(feed_id)
+    .execute(&mut *tx)
+    .await?;
+    
+    tx.commit().await?;
+    Ok(feed_id)
+}
+
 // Subscription management functions
 pub async fn subscribe_to_feed(pool: &PgPool, user_id: Uuid, feed_id: Uuid) -> Result<Subscription> {
     let subscription = sqlx::query_as::<_, Subscription>(
@@ -109,7 +563,7 @@ pub async fn subscribe_to_feed(pool: &PgPool, user_id: Uuid, feed_id: Uuid) -> R
         INSERT INTO subscriptions (user_id, feed_id)
         VALUES ($1, $2)
         ON CONFLICT (user_id, feed_id) DO UPDATE SET user_id = EXCLUDED.user_id
-        RETURNING id, user_id, feed_id, custom_title, created_at
+        RETURNING id, user_id, feed_id, custom_title, refresh_interval_secs, fetch_full_content, created_at
         "#
     )
     .bind(user_id)
@@ -132,31 +586,35 @@ pub async fn unsubscribe_from_feed(pool: &PgPool, user_id: Uuid, feed_id: Uuid)
     .execute(pool)
     .await?;
     
-    Ok(())
-}
-
-pub async fn get_user_subscriptions(pool: &PgPool, user_id: Uuid) -> Result<Vec<Subscription>> {
-    let subscriptions = sqlx::query_as::<_, Subscription>(
+    // A private feed has no one left to read it
+    sqlx::query(
         r#"
-        SELECT id, user_id, feed_id, custom_title, created_at
-        FROM subscriptions
-        WHERE user_id = $1
-        ORDER BY created_at DESC
+        DELETE FROM feeds
+        WHERE id = $1 AND owner_id = $2
         "#
     )
+    .bind(feed_id)
     .bind(user_id)
-    .fetch_all(pool)
+    .execute(pool)
     .await?;
     
-    Ok(subscriptions)
+    Ok(())
 }
 
 pub async fn get_user_subscriptions_with_labels(pool: &PgPool, user_id: Uuid) -> Result<Vec<SubscriptionWithLabels>> {
     let rows = sqlx::query(
         r#"
         SELECT 
-            s.id, s.user_id, s.feed_id, s.custom_title, s.created_at,
-            f.title as feed_title, f.url as feed_url,
+            s.id, s.user_id, s.feed_id, s.custom_title, s.refresh_interval_secs, s.fetch_full_content, s.created_at,
+            f.title as feed_title, f.url as feed_url, f.kind as feed_kind,
+            r.item_selector, r.title_selector, r.link_selector, r.date_selector, r.body_selector,
+            f.last_fetched, f.last_success_at, f.last_status, f.last_error, f.consecutive_failures, f.paused_at,
+            ARRAY(
+                SELECT h.name FROM subscription_request_headers h
+                WHERE h.subscription_id = s.id
+                ORDER BY h.name
+            ) as request_header_names,
+            EXISTS (SELECT 1 FROM subscription_credentials c WHERE c.subscription_id = s.id) as has_credentials,
             COALESCE(
                 json_agg(
                     json_build_object(
@@ -171,10 +629,11 @@ pub async fn get_user_subscriptions_with_labels(pool: &PgPool, user_id: Uuid) ->
             ) as labels
         FROM subscriptions s
         JOIN feeds f ON s.feed_id = f.id
+        LEFT JOIN scrape_rules r ON r.feed_id = f.id
         LEFT JOIN subscription_labels sl ON s.id = sl.subscription_id
         LEFT JOIN labels l ON sl.label_id = l.id
         WHERE s.user_id = $1
-        GROUP BY s.id, s.user_id, s.feed_id, s.custom_title, s.created_at, f.title, f.url
+        GROUP BY s.id, s.user_id, s.feed_id, s.custom_title, s.refresh_interval_secs, s.fetch_full_content, s.created_at, f.id, r.feed_id
         ORDER BY s.created_at DESC
         "#
     )
@@ -182,28 +641,298 @@ pub async fn get_user_subscriptions_with_labels(pool: &PgPool, user_id: Uuid) ->
     .fetch_all(pool)
     .await?;
     
-    let mut subscriptions = Vec::new();
-    for row in rows {
-        let subscription = Subscription {
-            id: row.get("id"),
-            user_id: row.get("user_id"),
-            feed_id: row.get("feed_id"),
-            custom_title: row.get("custom_title"),
-            created_at: row.get("created_at"),
-        };
-        
-        let labels_json: serde_json::Value = row.get("labels");
-        let labels: Vec<Label> = serde_json::from_value(labels_json)?;
+    let mut subscriptions = Vec::new();
+    for row in rows {
+        let subscription = Subscription {
+            id: row.get("id"),
+            user_id: row.get("user_id"),
+            feed_id: row.get("feed_id"),
+            custom_title: row.get("custom_title"),
+            refresh_interval_secs: row.get("refresh_interval_secs"),
+            fetch_full_content: row.get("fetch_full_content"),
+            created_at: row.get("created_at"),
+        };
+        
+        let labels_json: serde_json::Value = row.get("labels");
+        let labels: Vec<Label> = serde_json::from_value(labels_json)?;
+        
+        subscriptions.push(SubscriptionWithLabels {
+            subscription,
+            feed_title: row.get("feed_title"),
+            feed_url: row.get("feed_url"),
+            feed_kind: row.get("feed_kind"),
+            scrape_rules: row.get::<Option<String>, _>("item_selector").map(|item_selector| ScrapeRules {
+                item_selector,
+                title_selector: row.get("title_selector"),
+                link_selector: row.get("link_selector"),
+                date_selector: row.get("date_selector"),
+                body_selector: row.get("body_selector"),
+            }),
+            fetch_status: FeedFetchStatus {
+                last_fetched: row.get("last_fetched"),
+                last_success_at: row.get("last_success_at"),
+                last_status: row.get("last_status"),
+                last_error: row.get("last_error"),
+                consecutive_failures: row.get("consecutive_failures"),
+                paused_at: row.get("paused_at"),
+            },
+            request_header_names: row.get("request_header_names"),
+            has_credentials: row.get("has_credentials"),
+            labels,
+        });
+    }
+    
+    Ok(subscriptions)
+}
+
+pub async fn set_subscription_refresh_interval(
+    pool: &PgPool,
+    subscription_id: Uuid,
+    refresh_interval_secs: Option<i32>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE subscriptions 
+        SET refresh_interval_secs = $2
+        WHERE id = $1
+        "#
+    )
+    .bind(subscription_id)
+    .bind(refresh_interval_secs)
+    .execute(pool)
+    .await?;
+    
+    // Bring the next fetch forward so a shorter interval takes effect now
+    sqlx::query(
+        r#"
+        UPDATE feeds f
+        SET next_fetch_at = LEAST(f.next_fetch_at, NOW() + make_interval(secs => s.refresh_interval_secs))
+        FROM subscriptions s
+        WHERE s.id = $1 AND s.feed_id = f.id AND s.refresh_interval_secs IS NOT NULL
+        "#
+    )
+    .bind(subscription_id)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+pub async fn set_subscription_fetch_full_content(pool: &PgPool, subscription_id: Uuid, fetch_full_content: bool) -> Result<Uuid> {
+    let feed_id = sqlx::query_scalar(
+        r#"
+        UPDATE subscriptions
+        SET fetch_full_content = $2
+        WHERE id = $1
+        RETURNING feed_id
+        "#
+    )
+    .bind(subscription_id)
+    .bind(fetch_full_content)
+    .fetch_one(pool)
+    .await?;
+    
+    Ok(feed_id)
+}
+
+/// Custom headers to send when fetching a feed. Only a private feed has any:
+/// those of its owner's subscription.
+pub async fn get_feed_request_headers(pool: &PgPool, feed_id: Uuid) -> Result<Vec<(String, String)>> {
+    let headers = sqlx::query_as::<_, (String, String)>(
+        r#"
+        SELECT h.name, h.value
+        FROM subscription_request_headers h
+        JOIN subscriptions s ON s.id = h.subscription_id
+        JOIN feeds f ON f.id = s.feed_id AND f.owner_id = s.user_id
+        WHERE f.id = $1
+        ORDER BY h.name
+        "#
+    )
+    .bind(feed_id)
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(headers)
+}
+
+/// Replace a subscription's custom request headers. Returns the feed the
+/// subscription now points at (see `reassign_subscription_feed`).
+pub async fn set_subscription_request_headers(
+    pool: &PgPool,
+    subscription_id: Uuid,
+    headers: &[(String, String)],
+) -> Result<Uuid> {
+    let mut tx = pool.begin().await?;
+    
+    sqlx::query(
+        r#"
+        DELETE FROM subscription_request_headers WHERE subscription_id = $1
+        "#
+    )
+    .bind(subscription_id)
+    .execute(&mut *tx)
+    .await?;
+    
+    for (name, value) in headers {
+        sqlx::query(
+            r#"
+            INSERT INTO subscription_request_headers (subscription_id, name, value)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (subscription_id, name) DO UPDATE SET value = EXCLUDED.value
+            "#
+        )
+        .bind(subscription_id)
+        .bind(name)
+        .bind(value)
+        .execute(&mut *tx)
+        .await?;
+    }
+    
+    let feed_id = reassign_subscription_feed(&mut tx, subscription_id).await?;
+    tx.commit().await?;
+    Ok(feed_id)
+}
+
+/// HTTP Basic credentials for fetching a feed, still sealed. Only a private
+/// feed has any: those of its owner's subscription.
+pub async fn get_feed_credentials(pool: &PgPool, feed_id: Uuid) -> Result<Option<EncryptedValue>> {
+    let sealed = sqlx::query_as::<_, (String, String)>(
+        r#"
+        SELECT c.ciphertext, c.nonce
+        FROM subscription_credentials c
+        JOIN subscriptions s ON s.id = c.subscription_id
+        JOIN feeds f ON f.id = s.feed_id AND f.owner_id = s.user_id
+        WHERE f.id = $1
+        "#
+    )
+    .bind(feed_id)
+    .fetch_optional(pool)
+    .await?;
+    
+    Ok(sealed.map(|(ciphertext, nonce)| EncryptedValue { ciphertext, nonce }))
+}
+
+/// Store (or with `None`, remove) a subscription's sealed HTTP Basic
+/// credentials. Returns the feed the subscription now points at (see
+/// `reassign_subscription_feed`).
+pub async fn set_subscription_credentials(
+    pool: &PgPool,
+    subscription_id: Uuid,
+    sealed: Option<&EncryptedValue>,
+) -> Result<Uuid> {
+    let mut tx = pool.begin().await?;
+    
+    match sealed {
+        Some(sealed) => {
+            sqlx::query(
+                r#"
+                INSERT INTO subscription_credentials (subscription_id, ciphertext, nonce)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (subscription_id) DO UPDATE
+                SET ciphertext = EXCLUDED.ciphertext, nonce = EXCLUDED.nonce, updated_at = NOW()
+                "#
+            )
+            .bind(subscription_id)
+            .bind(&sealed.ciphertext)
+            .bind(&sealed.nonce)
+            .execute(&mut *tx)
+            .await?;
+        }
+        None => {
+            sqlx::query(
+                r#"
+                DELETE FROM subscription_credentials WHERE subscription_id = $1
+                "#
+            )
+            .bind(subscription_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    
+    let feed_id = reassign_subscription_feed(&mut tx, subscription_id).await?;
+    tx.commit().await?;
+    Ok(feed_id)
+}
+
+/// Point a subscription at the right copy of its feed: a private one while
+/// it has custom headers or credentials, the shared one otherwise. The feed
+/// it leaves is deleted if nobody else is subscribed to it.
+async fn reassign_subscription_feed(tx: &mut Transaction<'_, Postgres>, subscription_id: Uuid) -> Result<Uuid> {
+    let (user_id, old_feed_id, url, owner_id, kind, needs_private): (Uuid, Uuid, String, Option<Uuid>, FeedKind, bool) = sqlx::query_as(
+        r#"
+        SELECT s.user_id, f.id, f.url, f.owner_id, f.kind,
+               EXISTS (SELECT 1 FROM subscription_request_headers h WHERE h.subscription_id = s.id)
+               OR EXISTS (SELECT 1 FROM subscription_credentials c WHERE c.subscription_id = s.id)
+        FROM subscriptions s
+        JOIN feeds f ON f.id = s.feed_id
+        WHERE s.id = $1
+        "#
+    )
+    .bind(subscription_id)
+    .fetch_one(&mut **tx)
+    .await?;
+    
+    // Static and scraped feeds are always private to whoever set them up
+    if kind != FeedKind::Remote {
+        return Ok(old_feed_id);
+    }
+    
+    let new_feed_id: Uuid = match (needs_private, owner_id) {
+        (true, None) => {
+            sqlx::query_scalar(
+                r#"
+                INSERT INTO feeds (url, owner_id, title, description)
+                SELECT url, $2, title, description FROM feeds WHERE id = $1
+                ON CONFLICT (owner_id, url) WHERE owner_id IS NOT NULL DO UPDATE SET url = EXCLUDED.url
+                RETURNING id
+                "#
+            )
+            .bind(old_feed_id)
+            .bind(user_id)
+            .fetch_one(&mut **tx)
+            .await?
+        }
+        (false, Some(_)) => {
+            sqlx::query_scalar(
+                r#"
+                INSERT INTO feeds (url)
+                VALUES ($1)
+                ON CONFLICT (url) WHERE owner_id IS NULL DO UPDATE SET url = EXCLUDED.url
+                RETURNING id
+                "#
+            )
+            .bind(&url)
+            .fetch_one(&mut **tx)
+            .await?
+        }
+        _ => old_feed_id,
+    };
+    
+    if new_feed_id != old_feed_id {
+        sqlx::query(
+            r#"
+            UPDATE subscriptions SET feed_id = $2 WHERE id = $1
+            "#
+        )
+        .bind(subscription_id)
+        .bind(new_feed_id)
+        .execute(&mut **tx)
+        .await?;
         
-        subscriptions.push(SubscriptionWithLabels {
-            subscription,
-            feed_title: row.get("feed_title"),
-            feed_url: row.get("feed_url"),
-            labels,
-        });
+        // Drop the feed left behind if nobody else reads it
+        sqlx::query(
+            r#"
+            DELETE FROM feeds f
+            WHERE f.id = $1 AND NOT EXISTS (SELECT 1 FROM subscriptions s WHERE s.feed_id = f.id)
+            "#
+        )
+        .bind(old_feed_id)
+        .execute(&mut **tx)
+        .await?;
     }
     
-    Ok(subscriptions)
+    Ok(new_feed_id)
 }
 
 pub async fn user_owns_subscription(pool: &PgPool, user_id: Uuid, subscription_id: Uuid) -> Result<bool> {
@@ -319,43 +1048,151 @@ pub async fn clear_subscription_labels(pool: &PgPool, subscription_id: Uuid) ->
 }
 
 // Item management functions
-pub async fn create_or_update_item(
-    pool: &PgPool,
-    feed_id: Uuid,
-    guid: &str,
-    title: &str,
-    description: Option<&str>,
-    link: Option<&str>,
-    pub_date: Option<time::OffsetDateTime>,
-    author: Option<&str>,
-    content: Option<&str>,
-) -> Result<Uuid> {
+/// Insert or update an item, returning its id and whether it was newly inserted.
+/// When an update changes what the item says, the version it replaces is
+/// kept as a revision.
+pub async fn create_or_update_item(pool: &PgPool, feed_id: Uuid, item: &NewItem) -> Result<(Uuid, bool)> {
+    let mut tx = pool.begin().await?;
+    
+    let previous = sqlx::query_as::<_, (Uuid, String, Option<String>, Option<String>, bool)>(
+        r#"
+        SELECT id, title, description, content, full_content_stored
+        FROM items
+        WHERE feed_id = $1 AND guid = $2
+        FOR UPDATE
+        "#
+    )
+    .bind(feed_id)
+    .bind(&item.guid)
+    .fetch_optional(&mut *tx)
+    .await?;
+    
+    let mut revised = false;
+    if let Some((item_id, title, description, content, has_full_content)) = previous {
+        // Downloaded full content is not the feed's to change
+        revised = title.trim() != item.title.trim()
+            || revisions::text_changed(description.as_deref(), item.description.as_deref())
+            || (!has_full_content && revisions::text_changed(content.as_deref(), item.content.as_deref()));
+        if revised {
+            sqlx::query(
+                r#"
+                INSERT INTO item_revisions (item_id, title, description, content)
+                VALUES ($1, $2, $3, $4)
+                "#
+            )
+            .bind(item_id)
+            .bind(&title)
+            .bind(&description)
+            .bind(&content)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    
     let result = sqlx::query(
         r#"
-        INSERT INTO items (feed_id, guid, title, description, link, pub_date, author, content)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        INSERT INTO items (feed_id, guid, title, description, link, pub_date, author, content,
+                           duration_seconds, episode, season, image_url, thumbnail_url)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
         ON CONFLICT (feed_id, guid) DO UPDATE SET
             title = EXCLUDED.title,
             description = EXCLUDED.description,
             link = EXCLUDED.link,
             pub_date = EXCLUDED.pub_date,
             author = EXCLUDED.author,
-            content = EXCLUDED.content
-        RETURNING id
+            content = CASE WHEN items.full_content_stored THEN items.content ELSE EXCLUDED.content END,
+            duration_seconds = EXCLUDED.duration_seconds,
+            episode = EXCLUDED.episode,
+            season = EXCLUDED.season,
+            image_url = EXCLUDED.image_url,
+            thumbnail_url = EXCLUDED.thumbnail_url,
+            revised_at = CASE WHEN $14 THEN NOW() ELSE items.revised_at END
+        RETURNING id, (xmax = 0) AS inserted
         "#
     )
     .bind(feed_id)
-    .bind(guid)
-    .bind(title)
+    .bind(&item.guid)
+    .bind(&item.title)
+    .bind(&item.description)
+    .bind(&item.link)
+    .bind(item.pub_date)
+    .bind(&item.author)
+    .bind(&item.content)
+    .bind(item.duration_seconds)
+    .bind(item.episode)
+    .bind(item.season)
+    .bind(&item.image_url)
+    .bind(&item.thumbnail_url)
+    .bind(revised)
+    .fetch_one(&mut *tx)
+    .await?;
+    
+    let item_id: Uuid = result.get("id");
+    let inserted: bool = result.get("inserted");
+    
+    for enclosure in &item.enclosures {
+        sqlx::query(
+            r#"
+            INSERT INTO item_enclosures (item_id, url, mime_type, length, title, duration_seconds)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (item_id, url) DO UPDATE SET
+                mime_type = EXCLUDED.mime_type,
+                length = EXCLUDED.length,
+                title = EXCLUDED.title,
+                duration_seconds = EXCLUDED.duration_seconds
+            "#
+        )
+        .bind(item_id)
+        .bind(&enclosure.url)
+        .bind(&enclosure.mime_type)
+        .bind(enclosure.length)
+        .bind(&enclosure.title)
+        .bind(enclosure.duration_seconds)
+        .execute(&mut *tx)
+        .await?;
+    }
+    
+    tx.commit().await?;
+    Ok((item_id, inserted))
+}
+
+/// Items stored before ingest sanitized their HTML, at most `limit` of them.
+pub async fn get_unsanitized_items(pool: &PgPool, limit: i64) -> Result<Vec<ItemHtml>> {
+    let items = sqlx::query_as::<_, ItemHtml>(
+        r#"
+        SELECT id, link, description, content
+        FROM items
+        WHERE needs_sanitizing
+        LIMIT $1
+        "#
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(items)
+}
+
+pub async fn set_item_sanitized_html(
+    pool: &PgPool,
+    item_id: Uuid,
+    description: Option<&str>,
+    content: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE items
+        SET description = $2, content = $3, needs_sanitizing = FALSE
+        WHERE id = $1
+        "#
+    )
+    .bind(item_id)
     .bind(description)
-    .bind(link)
-    .bind(pub_date)
-    .bind(author)
     .bind(content)
-    .fetch_one(pool)
+    .execute(pool)
     .await?;
     
-    Ok(result.get(0))
+    Ok(())
 }
 
 pub async fn get_user_items(
@@ -368,13 +1205,16 @@ pub async fn get_user_items(
         r#"
         SELECT 
             i.id, i.feed_id, i.guid, i.title, i.description, i.link, 
-            i.pub_date, i.author, i.content, i.created_at,
+            i.pub_date, i.author, i.content, i.duration_seconds, i.episode, i.season, i.image_url,
+            i.thumbnail_url, i.created_at, i.revised_at,
             f.title as feed_title,
-            COALESCE(ur.is_read, false) as is_read
+            COALESCE(ur.is_read, false) as is_read,
+            pp.position_seconds as playback_position
         FROM items i
         JOIN feeds f ON i.feed_id = f.id
         JOIN subscriptions s ON s.feed_id = f.id
         LEFT JOIN user_read_items ur ON ur.item_id = i.id AND ur.user_id = $1
+        LEFT JOIN user_playback_positions pp ON pp.item_id = i.id AND pp.user_id = $1
         WHERE s.user_id = $1
         ORDER BY COALESCE(i.pub_date, i.created_at) DESC
         LIMIT $2 OFFSET $3
@@ -398,19 +1238,177 @@ pub async fn get_user_items(
             pub_date: row.get("pub_date"),
             author: row.get("author"),
             content: row.get("content"),
+            duration_seconds: row.get("duration_seconds"),
+            episode: row.get("episode"),
+            season: row.get("season"),
+            image_url: row.get("image_url"),
+            thumbnail_url: row.get("thumbnail_url"),
             created_at: row.get("created_at"),
+            revised_at: row.get("revised_at"),
         };
         
         items.push(ItemWithReadStatus {
             item,
             is_read: row.get("is_read"),
             feed_title: row.get("feed_title"),
+            enclosures: Vec::new(),
+            playback_position: row.get("playback_position"),
         });
     }
     
+    let item_ids: Vec<Uuid> = items.iter().map(|item| item.item.id).collect();
+    for enclosure in get_item_enclosures(pool, &item_ids).await? {
+        if let Some(item) = items.iter_mut().find(|item| item.item.id == enclosure.item_id) {
+            item.enclosures.push(enclosure);
+        }
+    }
+    
+    Ok(items)
+}
+
+/// An item, if it is in a feed the user subscribes to.
+pub async fn get_user_item(pool: &PgPool, user_id: Uuid, item_id: Uuid) -> Result<Option<Item>> {
+    let item = sqlx::query_as::<_, Item>(
+        r#"
+        SELECT i.id, i.feed_id, i.guid, i.title, i.description, i.link,
+               i.pub_date, i.author, i.content, i.duration_seconds, i.episode, i.season, i.image_url,
+               i.thumbnail_url, i.created_at, i.revised_at
+        FROM items i
+        JOIN subscriptions s ON s.feed_id = i.feed_id AND s.user_id = $1
+        WHERE i.id = $2
+        "#
+    )
+    .bind(user_id)
+    .bind(item_id)
+    .fetch_optional(pool)
+    .await?;
+    
+    Ok(item)
+}
+
+/// Earlier versions of an item, oldest first.
+pub async fn get_item_revisions(pool: &PgPool, item_id: Uuid) -> Result<Vec<ItemRevision>> {
+    let revisions = sqlx::query_as::<_, ItemRevision>(
+        r#"
+        SELECT title, description, content, created_at
+        FROM item_revisions
+        WHERE item_id = $1
+        ORDER BY created_at
+        "#
+    )
+    .bind(item_id)
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(revisions)
+}
+
+pub async fn get_item_enclosures(pool: &PgPool, item_ids: &[Uuid]) -> Result<Vec<Enclosure>> {
+    let enclosures = sqlx::query_as::<_, Enclosure>(
+        r#"
+        SELECT id, item_id, url, mime_type, length, title, duration_seconds
+        FROM item_enclosures
+        WHERE item_id = ANY($1)
+        ORDER BY created_at, url
+        "#
+    )
+    .bind(item_ids)
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(enclosures)
+}
+
+/// Remember how far into an item's media the user got. Returns false if the
+/// item is not in any feed they subscribe to.
+pub async fn set_playback_position(pool: &PgPool, user_id: Uuid, item_id: Uuid, position_seconds: f64) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO user_playback_positions (user_id, item_id, position_seconds)
+        SELECT $1, i.id, $3
+        FROM items i
+        JOIN subscriptions s ON s.feed_id = i.feed_id AND s.user_id = $1
+        WHERE i.id = $2
+        ON CONFLICT (user_id, item_id) DO UPDATE SET
+            position_seconds = EXCLUDED.position_seconds,
+            updated_at = NOW()
+        "#
+    )
+    .bind(user_id)
+    .bind(item_id)
+    .bind(position_seconds)
+    .execute(pool)
+    .await?;
+    
+    Ok(result.rows_affected() > 0)
+}
+
+/// The newest items whose articles have not been downloaded yet, from
+/// feeds where some subscriber wants full content.
+pub async fn get_items_needing_full_content(pool: &PgPool, limit: i64) -> Result<Vec<(Uuid, String)>> {
+    let items = sqlx::query_as::<_, (Uuid, String)>(
+        r#"
+        SELECT i.id, i.link
+        FROM items i
+        WHERE i.full_content_fetched_at IS NULL
+          AND (i.full_content_retry_at IS NULL OR i.full_content_retry_at <= NOW())
+          AND (i.link LIKE 'http://%' OR i.link LIKE 'https://%')
+          AND EXISTS (SELECT 1 FROM subscriptions s WHERE s.feed_id = i.feed_id AND s.fetch_full_content)
+        ORDER BY i.pub_date DESC NULLS LAST, i.created_at DESC
+        LIMIT $1
+        "#
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    
     Ok(items)
 }
 
+/// Store an item's extracted article, or just note the attempt when
+/// nothing could be extracted, clearing any failures before it. Only a
+/// stored article keeps feed updates from replacing the item's content.
+pub async fn set_item_full_content(pool: &PgPool, item_id: Uuid, content: Option<&str>) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE items
+        SET content = COALESCE($2, content),
+            full_content_stored = full_content_stored OR $2 IS NOT NULL,
+            full_content_fetched_at = NOW(),
+            full_content_attempts = 0,
+            full_content_retry_at = NULL
+        WHERE id = $1
+        "#
+    )
+    .bind(item_id)
+    .bind(content)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+/// Note a failed article download: it is retried after `retry_secs`,
+/// doubled for every earlier failure, until `max_attempts` have failed.
+pub async fn record_full_content_failure(pool: &PgPool, item_id: Uuid, retry_secs: i32, max_attempts: i32) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE items
+        SET full_content_attempts = full_content_attempts + 1,
+            full_content_retry_at = NOW() + make_interval(secs => $2::float8 * power(2, full_content_attempts)),
+            full_content_fetched_at = CASE WHEN full_content_attempts + 1 >= $3 THEN NOW() END
+        WHERE id = $1
+        "#
+    )
+    .bind(item_id)
+    .bind(retry_secs)
+    .bind(max_attempts)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
 pub async fn mark_items_read(pool: &PgPool, user_id: Uuid, item_ids: &[Uuid]) -> Result<()> {
     for item_id in item_ids {
         sqlx::query(
@@ -427,4 +1425,179 @@ pub async fn mark_items_read(pool: &PgPool, user_id: Uuid, item_ids: &[Uuid]) ->
     }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Whether a feed is fetched on behalf of everyone subscribed to its URL,
+/// rather than being a private copy or an uploaded feed.
+pub async fn is_public_remote_feed(pool: &PgPool, feed_id: Uuid) -> Result<bool> {
+    let public: Option<bool> = sqlx::query_scalar(
+        r#"
+        SELECT owner_id IS NULL AND kind = 'remote'
+        FROM feeds
+        WHERE id = $1
+        "#
+    )
+    .bind(feed_id)
+    .fetch_optional(pool)
+    .await?;
+    
+    Ok(public.unwrap_or(false))
+}
+
+pub async fn get_websub_subscription(pool: &PgPool, feed_id: Uuid) -> Result<Option<WebSubSubscription>> {
+    let subscription = sqlx::query_as::<_, WebSubSubscription>(
+        r#"
+        SELECT feed_id, hub_url, topic_url, secret, renew_at
+        FROM websub_subscriptions
+        WHERE feed_id = $1
+        "#
+    )
+    .bind(feed_id)
+    .fetch_optional(pool)
+    .await?;
+    
+    Ok(subscription)
+}
+
+/// Subscriptions whose lease is about to run out, or whose last request was
+/// never verified, and so need asking for again.
+pub async fn get_due_websub_subscriptions(pool: &PgPool) -> Result<Vec<WebSubSubscription>> {
+    let subscriptions = sqlx::query_as::<_, WebSubSubscription>(
+        r#"
+        SELECT feed_id, hub_url, topic_url, secret, renew_at
+        FROM websub_subscriptions
+        WHERE renew_at <= NOW()
+        ORDER BY renew_at
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(subscriptions)
+}
+
+/// Record that a subscription request is about to be sent to `hub_url`.
+/// An active subscription stays active while it is renewed at the same hub;
+/// anything else waits for the hub to verify it again.
+pub async fn request_websub_subscription(
+    pool: &PgPool,
+    feed_id: Uuid,
+    hub_url: &str,
+    topic_url: &str,
+    secret: &str,
+    retry_at: time::OffsetDateTime,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO websub_subscriptions (feed_id, hub_url, topic_url, secret, renew_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (feed_id) DO UPDATE
+        SET state = CASE
+                WHEN websub_subscriptions.state = 'active'
+                    AND websub_subscriptions.hub_url = EXCLUDED.hub_url
+                    AND websub_subscriptions.topic_url = EXCLUDED.topic_url
+                THEN 'active'
+                ELSE 'requested'
+            END,
+            hub_url = EXCLUDED.hub_url,
+            topic_url = EXCLUDED.topic_url,
+            secret = EXCLUDED.secret,
+            renew_at = EXCLUDED.renew_at,
+            updated_at = NOW()
+        "#
+    )
+    .bind(feed_id)
+    .bind(hub_url)
+    .bind(topic_url)
+    .bind(secret)
+    .bind(retry_at)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+/// The hub verified the subscription and granted a lease.
+pub async fn activate_websub_subscription(
+    pool: &PgPool,
+    feed_id: Uuid,
+    lease_seconds: i32,
+    expires_at: time::OffsetDateTime,
+    renew_at: time::OffsetDateTime,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE websub_subscriptions
+        SET state = 'active', lease_seconds = $2, expires_at = $3, renew_at = $4, updated_at = NOW()
+        WHERE feed_id = $1
+        "#
+    )
+    .bind(feed_id)
+    .bind(lease_seconds)
+    .bind(expires_at)
+    .bind(renew_at)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+/// The hub refused the subscription; ask again at `retry_at`.
+pub async fn deny_websub_subscription(pool: &PgPool, feed_id: Uuid, retry_at: time::OffsetDateTime) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE websub_subscriptions
+        SET state = 'denied', expires_at = NULL, renew_at = $2, updated_at = NOW()
+        WHERE feed_id = $1
+        "#
+    )
+    .bind(feed_id)
+    .bind(retry_at)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+pub async fn delete_websub_subscription(pool: &PgPool, feed_id: Uuid) -> Result<()> {
+    sqlx::query(
+        r#"
+        DELETE FROM websub_subscriptions WHERE feed_id = $1
+        "#
+    )
+    .bind(feed_id)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+pub async fn record_websub_push(pool: &PgPool, feed_id: Uuid) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE websub_subscriptions SET last_push_at = NOW() WHERE feed_id = $1
+        "#
+    )
+    .bind(feed_id)
+    .execute(pool)
+    .await?;
+    
+    Ok(())
+}
+
+/// Whether the feed's hub currently pushes its updates to us.
+pub async fn has_active_websub_subscription(pool: &PgPool, feed_id: Uuid) -> Result<bool> {
+    let active: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM websub_subscriptions
+            WHERE feed_id = $1 AND state = 'active' AND expires_at > NOW()
+        )
+        "#
+    )
+    .bind(feed_id)
+    .fetch_one(pool)
+    .await?;
+    
+    Ok(active)
+}
@@ -0,0 +1,304 @@
+use crate::{http_client::HttpClient, rss_fetcher::{self, HtmlPageError, ParsedFeed}};
+use anyhow::{Context, Result};
+use scraper::{Html, Selector};
+use url::Url;
+
+/// Feed MIME types advertised through `<link rel="alternate">`.
+const FEED_MIME_TYPES: [&str; 4] = [
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/feed+json",
+    "application/rdf+xml",
+];
+
+/// Paths probed, in order, when a page does not advertise any feed.
+const COMMON_FEED_PATHS: [&str; 7] = [
+    "/feed",
+    "/rss.xml",
+    "/atom.xml",
+    "/feed.xml",
+    "/index.xml",
+    "/rss",
+    "/feed.json",
+];
+
+/// A feed found while looking at an HTML page.
+#[derive(Debug, Clone)]
+pub struct DiscoveredFeed {
+    pub url: String,
+    pub title: Option<String>,
+    pub mime_type: Option<String>,
+}
+
+/// Where a web page given in place of a feed leads.
+pub enum PageFeeds {
+    /// The page's only feed, already fetched
+    Single { url: String, feed: ParsedFeed },
+    /// Several feeds for the user to pick from
+    Choice(Vec<DiscoveredFeed>),
+}
+
+/// Follow a web page the user gave in place of a feed to the feeds behind
+/// it. `request_headers` (custom headers and credentials) were given for
+/// `requested_url`, so a single feed is only fetched with them when it is
+/// on that same origin. When they cannot be carried along, to a feed on
+/// another site or through the chooser, the user is asked for the feed's
+/// own URL instead.
+pub async fn feeds_behind_page(
+    client: &HttpClient,
+    requested_url: &str,
+    page: &HtmlPageError,
+    request_headers: &[(String, String)],
+) -> Result<PageFeeds> {
+    let discovered = discover_feeds(client, &page.url, &page.html).await;
+    let same_origin = |url: &str| match (Url::parse(url), Url::parse(requested_url)) {
+        (Ok(a), Ok(b)) => a.origin() == b.origin(),
+        _ => false,
+    };
+
+    match discovered.as_slice() {
+        [] => anyhow::bail!("No feeds found at {}", page.url),
+        [only] if request_headers.is_empty() || same_origin(&only.url) => {
+            let feed = rss_fetcher::fetch_and_parse_feed(client, &only.url, request_headers)
+                .await
+                .context("Failed to parse feed")?;
+            Ok(PageFeeds::Single { url: only.url.clone(), feed })
+        }
+        _ if !request_headers.is_empty() => {
+            let urls: Vec<&str> = discovered.iter().map(|feed| feed.url.as_str()).collect();
+            anyhow::bail!(
+                "{} is a web page, not a feed. To send your headers or credentials, add the feed by its own URL: {}",
+                page.url,
+                urls.join(", ")
+            )
+        }
+        _ => Ok(PageFeeds::Choice(discovered)),
+    }
+}
+
+/// Whether a fetched document is an HTML page rather than a feed, judged by
+/// its `Content-Type` or, failing that, its leading markup.
+pub fn is_html_document(content_type: Option<&str>, content: &[u8]) -> bool {
+    if content_type.is_some_and(|ct| ct.to_ascii_lowercase().contains("html")) {
+        return true;
+    }
+    let head = String::from_utf8_lossy(&content[..content.len().min(512)]).to_ascii_lowercase();
+    let head = head.trim_start_matches('\u{feff}').trim_start();
+    head.starts_with("<!doctype html") || head.starts_with("<html")
+}
+
+/// Collect the feeds a page advertises with
+/// `<link rel="alternate" type="application/rss+xml" href="...">` and friends,
+/// resolving relative links against the page (or its `<base href>`).
+pub fn discover_feed_links(html: &str, page_url: &Url) -> Vec<DiscoveredFeed> {
+    let document = Html::parse_document(html);
+    let link_selector = Selector::parse(r#"link[rel~="alternate" i][href]"#).expect("valid selector");
+    let base_selector = Selector::parse("base[href]").expect("valid selector");
+
+    let base = document
+        .select(&base_selector)
+        .next()
+        .and_then(|base| base.value().attr("href"))
+        .and_then(|href| page_url.join(href).ok())
+        .unwrap_or_else(|| page_url.clone());
+
+    let mut feeds: Vec<DiscoveredFeed> = Vec::new();
+    for link in document.select(&link_selector) {
+        let element = link.value();
+        let Some(mime_type) = element.attr("type").map(|t| t.trim().to_ascii_lowercase()) else {
+            continue;
+        };
+        if !FEED_MIME_TYPES.contains(&mime_type.as_str()) {
+            continue;
+        }
+        let Some(url) = element.attr("href").and_then(|href| base.join(href.trim()).ok()) else {
+            continue;
+        };
+        if feeds.iter().any(|feed| feed.url == url.as_str()) {
+            continue;
+        }
+
+        feeds.push(DiscoveredFeed {
+            url: url.to_string(),
+            title: element.attr("title").map(str::trim).filter(|t| !t.is_empty()).map(str::to_string),
+            mime_type: Some(mime_type),
+        });
+    }
+
+    feeds
+}
+
+/// Find the feeds behind an HTML page: the ones it links to, or else the
+/// first of the usual feed locations on the same site that actually parses.
+pub async fn discover_feeds(client: &HttpClient, page_url: &str, html: &str) -> Vec<DiscoveredFeed> {
+    let Ok(page_url) = Url::parse(page_url) else {
+        return Vec::new();
+    };
+
+    let linked = discover_feed_links(html, &page_url);
+    if !linked.is_empty() {
+        return linked;
+    }
+
+    for path in COMMON_FEED_PATHS {
+        let Ok(candidate) = page_url.join(path) else {
+            continue;
+        };
+        if let Ok(parsed) = rss_fetcher::fetch_and_parse_feed(client, candidate.as_str(), &[]).await {
+            return vec![DiscoveredFeed {
+                url: candidate.to_string(),
+                title: Some(parsed.title).filter(|t| !t.is_empty()),
+                mime_type: None,
+            }];
+        }
+    }
+
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use actix_web::{dev::ServerHandle, web, App, HttpRequest, HttpResponse, HttpServer};
+    use std::sync::{Arc, Mutex};
+
+    const FEED: &str = r#"<rss version="2.0"><channel><title>Stand-in</title><link>http://example.com/</link>
+        <description>D</description><item><title>Post</title><guid>1</guid></item></channel></rss>"#;
+
+    /// Path and `Authorization` header of each request a stand-in received.
+    type Seen = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+    /// A stand-in site serving `pages` by path and recording the
+    /// `Authorization` header of every request it receives.
+    fn stand_in_site(
+        listener: std::net::TcpListener,
+        pages: Vec<(&'static str, &'static str, String)>,
+        seen: Seen,
+    ) -> ServerHandle {
+        let pages = Arc::new(pages);
+        let server = HttpServer::new(move || {
+            let pages = pages.clone();
+            let seen = seen.clone();
+            App::new().default_service(web::to(move |req: HttpRequest| {
+                let pages = pages.clone();
+                let seen = seen.clone();
+                async move {
+                    let authorization = req.headers().get("authorization").and_then(|v| v.to_str().ok()).map(str::to_string);
+                    seen.lock().unwrap().push((req.path().to_string(), authorization));
+                    match pages.iter().find(|(path, ..)| *path == req.path()) {
+                        Some((_, content_type, body)) => HttpResponse::Ok().content_type(*content_type).body(body.clone()),
+                        None => HttpResponse::NotFound().finish(),
+                    }
+                }
+            }))
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+        handle
+    }
+
+    #[test]
+    fn recognizes_html_documents() {
+        assert!(is_html_document(Some("text/html; charset=utf-8"), b"whatever"));
+        assert!(is_html_document(Some("application/XHTML+xml"), b""));
+        assert!(is_html_document(None, b"\xEF\xBB\xBF  <!DOCTYPE html><html></html>"));
+        assert!(is_html_document(Some("text/plain"), b"\n<HTML><body></body></HTML>"));
+        assert!(!is_html_document(Some("application/rss+xml"), b"<?xml version=\"1.0\"?><rss></rss>"));
+        assert!(!is_html_document(None, br#"{"version": "https://jsonfeed.org/version/1.1"}"#));
+    }
+
+    #[test]
+    fn finds_advertised_feeds() {
+        let page_url = Url::parse("https://blog.example/posts/hello").unwrap();
+        let html = r#"<html><head>
+            <link rel="alternate" type="application/rss+xml" title=" Posts " href="/feed.xml">
+            <link rel="Alternate" type="Application/Atom+XML" href="atom.xml">
+            <link rel="alternate" type="application/rss+xml" href="https://blog.example/feed.xml">
+            <link rel="alternate" type="text/html" hreflang="fr" href="/fr/">
+            <link rel="alternate stylesheet" type="application/feed+json" title="" href="//cdn.example/feed.json">
+            <link rel="alternate" href="/untyped.xml">
+            <link rel="stylesheet" type="application/rss+xml" href="/not-a-feed.xml">
+        </head><body></body></html>"#;
+
+        let feeds = discover_feed_links(html, &page_url);
+        let urls: Vec<_> = feeds.iter().map(|feed| feed.url.as_str()).collect();
+        assert_eq!(urls, ["https://blog.example/feed.xml", "https://blog.example/posts/atom.xml", "https://cdn.example/feed.json"]);
+        assert_eq!(feeds[0].title.as_deref(), Some("Posts"));
+        assert_eq!(feeds[1].mime_type.as_deref(), Some("application/atom+xml"));
+        assert_eq!(feeds[2].title, None);
+    }
+
+    #[test]
+    fn resolves_against_base_href() {
+        let page_url = Url::parse("https://blog.example/posts/hello").unwrap();
+        let html = r#"<head><base href="https://static.example/site/">
+            <link rel="alternate" type="application/rss+xml" href="rss.xml"></head>"#;
+        let feeds = discover_feed_links(html, &page_url);
+        assert_eq!(feeds.len(), 1);
+        assert_eq!(feeds[0].url, "https://static.example/site/rss.xml");
+    }
+
+    /// Credentials given for one site stay there when its page points at a
+    /// feed on another.
+    #[actix_web::test]
+    async fn keeps_credentials_on_their_origin() {
+        let site_listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let other_listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let site = format!("http://{}", site_listener.local_addr().unwrap());
+        let other = format!("http://{}", other_listener.local_addr().unwrap());
+
+        let page = |href: String| format!(r#"<!DOCTYPE html><html><head><link rel="alternate" type="application/rss+xml" href="{}"></head></html>"#, href);
+        let site_seen = Seen::default();
+        let other_seen = Seen::default();
+        let site_handle = stand_in_site(
+            site_listener,
+            vec![
+                ("/local", "text/html", page("/feed.xml".to_string())),
+                ("/remote", "text/html", page(format!("{}/feed.xml", other))),
+                ("/feed.xml", "application/rss+xml", FEED.to_string()),
+            ],
+            site_seen.clone(),
+        );
+        let other_handle = stand_in_site(other_listener, vec![("/feed.xml", "application/rss+xml", FEED.to_string())], other_seen.clone());
+
+        let client = HttpClient::new(&Config::for_tests()).unwrap();
+        let authorization = "Basic dTpw".to_string();
+        let headers = vec![("authorization".to_string(), authorization.clone())];
+        let behind = |path: &str, headers: Vec<(String, String)>| {
+            let url = format!("{}{}", site, path);
+            let client = client.clone();
+            async move {
+                let page = rss_fetcher::fetch_and_parse_feed(&client, &url, &headers)
+                    .await
+                    .unwrap_err()
+                    .downcast::<HtmlPageError>()
+                    .unwrap();
+                feeds_behind_page(&client, &url, &page, &headers).await
+            }
+        };
+
+        // Same origin: the feed is fetched with the credentials
+        match behind("/local", headers.clone()).await.unwrap() {
+            PageFeeds::Single { url, feed } => {
+                assert_eq!(url, format!("{}/feed.xml", site));
+                assert_eq!(feed.title, "Stand-in");
+            }
+            PageFeeds::Choice(_) => panic!("expected a single feed"),
+        }
+        assert!(site_seen.lock().unwrap().iter().all(|(_, seen)| seen.as_ref() == Some(&authorization)));
+
+        // Another origin: refused with credentials, fetched without them
+        assert!(behind("/remote", headers).await.is_err());
+        assert!(other_seen.lock().unwrap().is_empty());
+        assert!(matches!(behind("/remote", Vec::new()).await.unwrap(), PageFeeds::Single { .. }));
+        assert_eq!(*other_seen.lock().unwrap(), [("/feed.xml".to_string(), None)]);
+
+        site_handle.stop(true).await;
+        other_handle.stop(true).await;
+    }
+}
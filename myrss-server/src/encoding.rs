@@ -0,0 +1,160 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use std::borrow::Cow;
+
+/// Transcode a fetched document to UTF-8 so the feed parsers, which only
+/// understand UTF-8, see the right characters.
+///
+/// The encoding is taken from, in order of precedence: a byte order mark,
+/// the `charset` of the HTTP `Content-Type`, and the XML declaration. When
+/// the winner claims UTF-8 but the bytes are not valid UTF-8, the next
+/// candidate is used instead, since servers often tack `charset=utf-8` onto
+/// everything. A document that declares nothing and is not valid UTF-8 is
+/// read as Windows-1252, the usual encoding of such legacy feeds. The XML
+/// declaration of a transcoded document is rewritten to say UTF-8.
+pub fn decode_to_utf8<'a>(content: &'a [u8], content_type: Option<&str>) -> Cow<'a, [u8]> {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(content) {
+        if encoding == UTF_8 {
+            return Cow::Borrowed(&content[bom_length..]);
+        }
+        let (text, _) = encoding.decode_without_bom_handling(&content[bom_length..]);
+        return Cow::Owned(declare_utf8(&text).into_bytes());
+    }
+
+    let candidates = [
+        content_type.and_then(charset_from_content_type),
+        xml_declared_encoding(content),
+    ];
+    let encoding = candidates
+        .iter()
+        .flatten()
+        .copied()
+        .find(|encoding| *encoding != UTF_8 || std::str::from_utf8(content).is_ok())
+        .unwrap_or_else(|| if std::str::from_utf8(content).is_ok() { UTF_8 } else { WINDOWS_1252 });
+
+    if encoding == UTF_8 {
+        return Cow::Borrowed(content);
+    }
+    let (text, _) = encoding.decode_without_bom_handling(content);
+    Cow::Owned(declare_utf8(&text).into_bytes())
+}
+
+/// The encoding named by a `charset` parameter, e.g. `text/xml; charset="ISO-8859-1"`.
+fn charset_from_content_type(content_type: &str) -> Option<&'static Encoding> {
+    content_type
+        .split(';')
+        .skip(1)
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("charset"))
+        .and_then(|(_, value)| Encoding::for_label(value.trim().trim_matches('"').as_bytes()))
+}
+
+/// The `encoding` pseudo-attribute of `<?xml ... ?>`. A declaration written in
+/// UTF-16 without a BOM is recognised by its byte pattern.
+fn xml_declared_encoding(content: &[u8]) -> Option<&'static Encoding> {
+    if content.starts_with(b"<\0?\0") {
+        return Some(UTF_16LE);
+    }
+    if content.starts_with(b"\0<\0?") {
+        return Some(UTF_16BE);
+    }
+
+    let declaration = content.strip_prefix(b"<?xml")?;
+    let end = declaration.windows(2).position(|w| w == b"?>")?;
+    let declaration = std::str::from_utf8(&declaration[..end]).ok()?;
+    let (_, value) = encoding_attribute(declaration)?;
+    Encoding::for_label(value.as_bytes())
+}
+
+/// Locate `encoding="..."` inside the body of an XML declaration, returning
+/// the byte range of the value and the value itself.
+fn encoding_attribute(declaration: &str) -> Option<(std::ops::Range<usize>, &str)> {
+    let start = declaration.find("encoding")? + "encoding".len();
+    let rest = &declaration[start..];
+    let eq = rest.find('=')?;
+    if !rest[..eq].trim().is_empty() {
+        return None;
+    }
+    let after_eq = &rest[eq + 1..];
+    let quote_offset = after_eq.find(['"', '\''])?;
+    let quote = after_eq[quote_offset..].chars().next()?;
+    let value_start = start + eq + 1 + quote_offset + 1;
+    let value_len = declaration[value_start..].find(quote)?;
+    let range = value_start..value_start + value_len;
+    Some((range.clone(), &declaration[range]))
+}
+
+/// Rewrite the XML declaration of an already transcoded document to match.
+fn declare_utf8(text: &str) -> String {
+    let Some(declaration) = text.strip_prefix("<?xml") else {
+        return text.to_string();
+    };
+    let Some(end) = declaration.find("?>") else {
+        return text.to_string();
+    };
+    match encoding_attribute(&declaration[..end]) {
+        Some((range, _)) => {
+            let offset = "<?xml".len();
+            format!("{}UTF-8{}", &text[..offset + range.start], &text[offset + range.end..])
+        }
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(content: &[u8], content_type: Option<&str>) -> String {
+        String::from_utf8(decode_to_utf8(content, content_type).into_owned()).unwrap()
+    }
+
+    #[test]
+    fn byte_order_mark_wins() {
+        let mut content = vec![0xFF, 0xFE];
+        for unit in "<?xml version=\"1.0\" encoding=\"UTF-16\"?><rss>é</rss>".encode_utf16() {
+            content.extend_from_slice(&unit.to_le_bytes());
+        }
+        assert_eq!(
+            decode(&content, Some("text/xml; charset=iso-8859-1")),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><rss>é</rss>"
+        );
+        assert_eq!(decode(b"\xEF\xBB\xBF<rss>\xC3\xA9</rss>", Some("text/xml; charset=iso-8859-1")), "<rss>é</rss>");
+    }
+
+    #[test]
+    fn content_type_charset_beats_declaration() {
+        let content = b"<?xml version='1.0' encoding='UTF-8'?><rss>caf\xE9</rss>";
+        assert_eq!(
+            decode(content, Some(r#"application/rss+xml; charset="ISO-8859-1""#)),
+            "<?xml version='1.0' encoding='UTF-8'?><rss>café</rss>"
+        );
+
+        let content = "<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><rss>café</rss>";
+        assert_eq!(decode(content.as_bytes(), Some("text/xml; charset=utf-8")), content);
+    }
+
+    #[test]
+    fn declaration_alone_is_honoured() {
+        let content = b"<?xml version=\"1.0\" encoding=\"windows-1252\"?><rss>\x93quoted\x94 \x80 5</rss>";
+        assert_eq!(
+            decode(content, Some("application/xml")),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><rss>\u{201C}quoted\u{201D} € 5</rss>"
+        );
+    }
+
+    #[test]
+    fn invalid_utf8_claim_falls_through() {
+        let content = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><rss>na\xEFve</rss>";
+        assert_eq!(
+            decode(content, Some("text/xml; charset=utf-8")),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><rss>naïve</rss>"
+        );
+    }
+
+    #[test]
+    fn undeclared_bodies() {
+        let utf8 = "<rss>café</rss>".as_bytes();
+        assert!(matches!(decode_to_utf8(utf8, None), Cow::Borrowed(_)));
+        assert_eq!(decode(b"<rss>caf\xE9</rss>", Some("text/xml")), "<rss>café</rss>");
+    }
+}
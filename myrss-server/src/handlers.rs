@@ -1,4 +1,19 @@
-use crate::{auth, db, models::*, rss_fetcher, templates, AppState};
+use crate::{
+    auth,
+    credentials::BasicCredentials,
+    db,
+    discovery,
+    http_client,
+    local_feeds,
+    models::*,
+    revisions,
+    rss_fetcher,
+    scheduler,
+    scrape,
+    templates,
+    websub,
+    AppState,
+};
 use actix_session::Session;
 use actix_web::{web, HttpRequest, HttpResponse, Result};
 use maud::Markup;
@@ -11,13 +26,24 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
         .service(web::resource("/logout").route(web::post().to(auth::logout)))
         .service(web::resource("/feeds").route(web::get().to(feeds)))
         .service(web::resource("/feeds/add").route(web::post().to(add_feed)))
+        .service(web::resource("/feeds/scrape").route(web::post().to(add_scraped_feed)))
         .service(web::resource("/feeds/{id}/labels").route(web::post().to(update_feed_labels)))
+        .service(web::resource("/feeds/{id}/interval").route(web::post().to(update_refresh_interval)))
+        .service(web::resource("/feeds/{id}/full-content").route(web::post().to(update_full_content)))
+        .service(web::resource("/feeds/{id}/headers").route(web::post().to(update_request_headers)))
+        .service(web::resource("/feeds/{id}/credentials").route(web::post().to(update_credentials)))
+        .service(web::resource("/feeds/{id}/scrape-rules").route(web::post().to(update_scrape_rules)))
+        .service(web::resource("/feeds/{id}/upload").route(web::post().to(upload_static_feed)))
+        .service(web::resource("/feeds/{id}/resume").route(web::post().to(resume_feed)))
         .service(web::resource("/feeds/{id}/unsubscribe").route(web::post().to(unsubscribe)))
         .service(web::resource("/labels").route(web::get().to(manage_labels)))
         .service(web::resource("/labels/add").route(web::post().to(add_label)))
         .service(web::resource("/labels/{id}/delete").route(web::post().to(delete_label)))
         .service(web::resource("/refresh").route(web::get().to(refresh_feeds)))
-        .service(web::resource("/api/items/mark-read").route(web::post().to(mark_read)));
+        .service(web::resource("/items/{id}/revisions").route(web::get().to(item_revisions)))
+        .service(web::resource("/api/items/mark-read").route(web::post().to(mark_read)))
+        .service(web::resource("/api/items/{id}/position").route(web::post().to(save_playback_position)))
+        .service(web::resource("/websub/{feed_id}").route(web::get().to(websub_verify)).route(web::post().to(websub_deliver)));
 }
 
 async fn require_auth(session: &Session, state: &AppState) -> Result<User> {
@@ -76,7 +102,17 @@ async fn feeds(session: Session, state: web::Data<AppState>) -> Result<HttpRespo
             actix_web::error::ErrorInternalServerError("Failed to get labels")
         })?;
     
-    let html: Markup = templates::feeds_page(&user.username, &subscriptions, &labels);
+    let local_feeds = match local_feeds::watch_url_prefix(&state.config) {
+        Some(prefix) => db::get_feeds_with_url_prefix(&state.db_pool, &prefix)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to get local feeds: {}", e);
+                actix_web::error::ErrorInternalServerError("Failed to get local feeds")
+            })?,
+        None => Vec::new(),
+    };
+    
+    let html: Markup = templates::feeds_page(&user.username, &subscriptions, &labels, &local_feeds);
     Ok(HttpResponse::Ok().content_type("text/html").body(html.into_string()))
 }
 
@@ -101,32 +137,136 @@ async fn add_feed(
         return Ok(HttpResponse::BadRequest().body("Please provide a URL or RSS content"));
     };
     
+    // A pasted document is stored as a static feed instead of being fetched
+    if form.url.as_deref().is_none_or(str::is_empty) {
+        return add_static_feed(&state, &user, &feed_content, form.labels.as_deref()).await;
+    }
+    
+    let request_headers = request_headers_from_form(
+        form.headers.as_deref().unwrap_or_default(),
+        form.cookies.as_deref().unwrap_or_default(),
+    )?;
+    let credentials = form.auth_username
+        .as_deref()
+        .map(str::trim)
+        .filter(|username| !username.is_empty())
+        .map(|username| BasicCredentials {
+            username: username.to_string(),
+            password: form.auth_password.clone().unwrap_or_default(),
+        });
+    let mut fetch_headers = request_headers.clone();
+    if let Some(credentials) = &credentials {
+        fetch_headers.push(("authorization".to_string(), credentials.authorization()));
+    }
+    
     // Try to parse the feed first
-    let channel = rss_fetcher::fetch_and_parse_feed(&feed_content)
-        .await
-        .map_err(|e| {
-            log::error!("Failed to parse feed: {}", e);
-            actix_web::error::ErrorBadRequest(format!("Failed to parse feed: {}", e))
-        })?;
+    let mut feed_url = feed_content;
+    let parsed = match rss_fetcher::fetch_and_parse_feed(&state.http_client, &feed_url, &fetch_headers).await {
+        Ok(parsed) => parsed,
+        Err(e) => match e.downcast::<rss_fetcher::HtmlPageError>() {
+            // A web page rather than a feed: look for the feeds it advertises
+            Ok(page) => match discovery::feeds_behind_page(&state.http_client, &feed_url, &page, &fetch_headers).await {
+                Ok(discovery::PageFeeds::Single { url, feed }) => {
+                    feed_url = url;
+                    feed
+                }
+                Ok(discovery::PageFeeds::Choice(discovered)) => {
+                    let labels = form.labels.clone().unwrap_or_default();
+                    let html: Markup = templates::feed_chooser_page(&user.username, &page.url, &discovered, &labels);
+                    return Ok(HttpResponse::Ok().content_type("text/html").body(html.into_string()));
+                }
+                Err(e) => {
+                    log::error!("Failed to add feed from {}: {:#}", page.url, e);
+                    return Err(actix_web::error::ErrorBadRequest(format!("{:#}", e)));
+                }
+            },
+            Err(e) => {
+                log::error!("Failed to parse feed: {}", e);
+                return Err(actix_web::error::ErrorBadRequest(format!("Failed to parse feed: {}", e)));
+            }
+        },
+    };
     
     // Create or get the feed
-    let feed_url = form.url.as_deref().unwrap_or(&feed_content);
-    let feed = db::create_or_get_feed(&state.db_pool, feed_url)
+    let feed = db::create_or_get_feed(&state.db_pool, &feed_url)
         .await
         .map_err(|e| {
             log::error!("Failed to create feed: {}", e);
             actix_web::error::ErrorInternalServerError("Failed to create feed")
         })?;
     
+    // Subscribe the user to the feed
+    let subscription = db::subscribe_to_feed(&state.db_pool, user.id, feed.id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to subscribe to feed: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to subscribe to feed")
+        })?;
+    
+    // Content fetched with the user's own headers or credentials goes into
+    // their private copy of the feed, never the shared one
+    let mut feed_id = feed.id;
+    if !request_headers.is_empty() {
+        feed_id = db::set_subscription_request_headers(&state.db_pool, subscription.id, &request_headers)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to store request headers: {}", e);
+                actix_web::error::ErrorInternalServerError("Failed to store request headers")
+            })?;
+    }
+    if let Some(credentials) = &credentials {
+        let sealed = credentials.seal(&state.config.credentials_key)
+            .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+        feed_id = db::set_subscription_credentials(&state.db_pool, subscription.id, Some(&sealed))
+            .await
+            .map_err(|e| {
+                log::error!("Failed to store credentials: {}", e);
+                actix_web::error::ErrorInternalServerError("Failed to store credentials")
+            })?;
+    }
+    
     // Update feed items
-    rss_fetcher::update_feed_items(&state.db_pool, feed.id, &channel)
+    rss_fetcher::update_feed_items(&state.db_pool, feed_id, &parsed)
         .await
         .map_err(|e| {
             log::error!("Failed to update feed items: {}", e);
             actix_web::error::ErrorInternalServerError("Failed to update feed items")
         })?;
     
-    // Subscribe the user to the feed
+    if let Err(e) = db::record_fetch_success(&state.db_pool, feed_id, None).await {
+        log::error!("Failed to record fetch status: {}", e);
+    }
+    
+    if let Err(e) = websub::ensure_subscription(&state.db_pool, &state.http_client, &state.config, feed_id, &feed_url, &parsed.websub).await {
+        log::warn!("Failed to subscribe to WebSub hub for {}: {:#}", feed_url, e);
+    }
+    
+    // Add labels if provided
+    if let Some(label_names) = &form.labels {
+        add_labels(&state, user.id, subscription.id, label_names).await;
+    }
+    
+    Ok(HttpResponse::SeeOther()
+        .append_header(("Location", "/feeds"))
+        .finish())
+}
+
+async fn add_static_feed(
+    state: &AppState,
+    user: &User,
+    content: &str,
+    label_names: Option<&[String]>,
+) -> Result<HttpResponse> {
+    let parsed = rss_fetcher::parse_feed(content.as_bytes())
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Failed to parse feed: {}", e)))?;
+    
+    let feed = db::create_static_feed(&state.db_pool, user.id, content)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to create feed: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to create feed")
+        })?;
+    
     let subscription = db::subscribe_to_feed(&state.db_pool, user.id, feed.id)
         .await
         .map_err(|e| {
@@ -134,13 +274,10 @@ async fn add_feed(
             actix_web::error::ErrorInternalServerError("Failed to subscribe to feed")
         })?;
     
-    // Add labels if provided
-    if let Some(label_names) = &form.labels {
-        for label_name in label_names {
-            if let Ok(label) = db::get_or_create_label(&state.db_pool, user.id, label_name).await {
-                let _ = db::add_label_to_subscription(&state.db_pool, subscription.id, label.id).await;
-            }
-        }
+    store_static_feed_items(state, feed.id, &parsed).await?;
+    
+    if let Some(label_names) = label_names {
+        add_labels(state, user.id, subscription.id, label_names).await;
     }
     
     Ok(HttpResponse::SeeOther()
@@ -148,6 +285,182 @@ async fn add_feed(
         .finish())
 }
 
+async fn add_scraped_feed(
+    session: Session,
+    state: web::Data<AppState>,
+    form: web::Form<AddScrapedFeedRequest>,
+) -> Result<HttpResponse> {
+    let user = require_auth(&session, &state).await?;
+    let form = form.into_inner();
+    
+    let rules = scrape::check_rules(form.rules)
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+    let request_headers = request_headers_from_form(
+        form.headers.as_deref().unwrap_or_default(),
+        form.cookies.as_deref().unwrap_or_default(),
+    )?;
+    let url = form.url.trim();
+    
+    // Try the rules on the page before saving them
+    let fetched = rss_fetcher::fetch_scraped_page(
+        &state.http_client,
+        url,
+        &rss_fetcher::CacheValidators::default(),
+        &request_headers,
+        &rules,
+    )
+    .await
+    .map_err(|e| actix_web::error::ErrorBadRequest(format!("Failed to scrape page: {}", e)))?;
+    let rss_fetcher::FetchOutcome::Fetched { feed: parsed, .. } = fetched else {
+        return Err(actix_web::error::ErrorBadRequest("Unexpected 304 Not Modified for unconditional request"));
+    };
+    if parsed.items.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest(format!("No items with a title matched `{}`", rules.item_selector)));
+    }
+    
+    let feed = db::create_scraped_feed(&state.db_pool, user.id, url, &rules)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to create feed: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to create feed")
+        })?
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("You already have a feed for this page"))?;
+    
+    let subscription = db::subscribe_to_feed(&state.db_pool, user.id, feed.id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to subscribe to feed: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to subscribe to feed")
+        })?;
+    
+    if !request_headers.is_empty() {
+        db::set_subscription_request_headers(&state.db_pool, subscription.id, &request_headers)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to store request headers: {}", e);
+                actix_web::error::ErrorInternalServerError("Failed to store request headers")
+            })?;
+    }
+    
+    rss_fetcher::update_feed_items(&state.db_pool, feed.id, &parsed)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to update feed items: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to update feed items")
+        })?;
+    
+    if let Err(e) = db::record_fetch_success(&state.db_pool, feed.id, None).await {
+        log::error!("Failed to record fetch status: {}", e);
+    }
+    
+    Ok(HttpResponse::SeeOther()
+        .append_header(("Location", "/feeds"))
+        .finish())
+}
+
+async fn update_scrape_rules(
+    session: Session,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    form: web::Form<ScrapeRules>,
+) -> Result<HttpResponse> {
+    let user = require_auth(&session, &state).await?;
+    let subscription_id = path.into_inner();
+    
+    // Verify the subscription belongs to the user
+    if !db::user_owns_subscription(&state.db_pool, user.id, subscription_id).await
+        .map_err(|e| {
+            log::error!("Failed to check subscription ownership: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to check subscription ownership")
+        })? {
+        return Err(actix_web::error::ErrorForbidden("Access denied"));
+    }
+    
+    let rules = scrape::check_rules(form.into_inner())
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+    
+    let feed_id = db::update_subscription_scrape_rules(&state.db_pool, subscription_id, &rules)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to update scrape rules: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to update scrape rules")
+        })?
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("Only scraped feeds have scrape rules"))?;
+    
+    refresh_changed_feed(&state, feed_id).await;
+    
+    Ok(HttpResponse::SeeOther()
+        .append_header(("Location", "/feeds"))
+        .finish())
+}
+
+async fn upload_static_feed(
+    session: Session,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    form: web::Form<UploadStaticFeedRequest>,
+) -> Result<HttpResponse> {
+    let user = require_auth(&session, &state).await?;
+    let subscription_id = path.into_inner();
+    
+    // Verify the subscription belongs to the user
+    if !db::user_owns_subscription(&state.db_pool, user.id, subscription_id).await
+        .map_err(|e| {
+            log::error!("Failed to check subscription ownership: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to check subscription ownership")
+        })? {
+        return Err(actix_web::error::ErrorForbidden("Access denied"));
+    }
+    
+    let feed_id = db::get_subscription_static_feed(&state.db_pool, subscription_id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to load feed: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to load feed")
+        })?
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("Only uploaded feeds can be re-uploaded"))?;
+    
+    let parsed = rss_fetcher::parse_feed(form.content.as_bytes())
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Failed to parse feed: {}", e)))?;
+    
+    db::update_static_feed_document(&state.db_pool, feed_id, &form.content)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to store feed document: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to store feed document")
+        })?;
+    
+    // Items are upserted by guid, so a newer version adds its new items and
+    // updates the ones already seen
+    store_static_feed_items(&state, feed_id, &parsed).await?;
+    
+    Ok(HttpResponse::SeeOther()
+        .append_header(("Location", "/feeds"))
+        .finish())
+}
+
+async fn store_static_feed_items(state: &AppState, feed_id: Uuid, parsed: &rss_fetcher::ParsedFeed) -> Result<()> {
+    rss_fetcher::update_feed_items(&state.db_pool, feed_id, parsed)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to update feed items: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to update feed items")
+        })?;
+    
+    if let Err(e) = db::record_fetch_success(&state.db_pool, feed_id, None).await {
+        log::error!("Failed to record fetch status: {}", e);
+    }
+    Ok(())
+}
+
+async fn add_labels(state: &AppState, user_id: Uuid, subscription_id: Uuid, label_names: &[String]) {
+    for label_name in label_names {
+        if let Ok(label) = db::get_or_create_label(&state.db_pool, user_id, label_name).await {
+            let _ = db::add_label_to_subscription(&state.db_pool, subscription_id, label.id).await;
+        }
+    }
+}
+
 async fn update_feed_labels(
     session: Session,
     state: web::Data<AppState>,
@@ -185,6 +498,212 @@ async fn update_feed_labels(
         .finish())
 }
 
+async fn update_refresh_interval(
+    session: Session,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    form: web::Form<UpdateRefreshIntervalRequest>,
+) -> Result<HttpResponse> {
+    let user = require_auth(&session, &state).await?;
+    let subscription_id = path.into_inner();
+    
+    // Verify the subscription belongs to the user
+    if !db::user_owns_subscription(&state.db_pool, user.id, subscription_id).await
+        .map_err(|e| {
+            log::error!("Failed to check subscription ownership: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to check subscription ownership")
+        })? {
+        return Err(actix_web::error::ErrorForbidden("Access denied"));
+    }
+    
+    let refresh_interval_secs = match form.refresh_interval_secs.trim() {
+        "" => None,
+        secs => Some(
+            secs.parse::<i32>()
+                .map_err(|_| actix_web::error::ErrorBadRequest("Invalid refresh interval"))?
+                .clamp(scheduler::MIN_FETCH_INTERVAL_SECS, scheduler::MAX_FETCH_INTERVAL_SECS),
+        ),
+    };
+    
+    db::set_subscription_refresh_interval(&state.db_pool, subscription_id, refresh_interval_secs)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to update refresh interval: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to update refresh interval")
+        })?;
+    
+    Ok(HttpResponse::SeeOther()
+        .append_header(("Location", "/feeds"))
+        .finish())
+}
+
+async fn update_full_content(
+    session: Session,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    form: web::Form<UpdateFullContentRequest>,
+) -> Result<HttpResponse> {
+    let user = require_auth(&session, &state).await?;
+    let subscription_id = path.into_inner();
+    
+    // Verify the subscription belongs to the user
+    if !db::user_owns_subscription(&state.db_pool, user.id, subscription_id).await
+        .map_err(|e| {
+            log::error!("Failed to check subscription ownership: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to check subscription ownership")
+        })? {
+        return Err(actix_web::error::ErrorForbidden("Access denied"));
+    }
+    
+    // Articles are downloaded by the background worker
+    db::set_subscription_fetch_full_content(&state.db_pool, subscription_id, form.fetch_full_content.is_some())
+        .await
+        .map_err(|e| {
+            log::error!("Failed to update full content setting: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to update full content setting")
+        })?;
+    
+    Ok(HttpResponse::SeeOther()
+        .append_header(("Location", "/feeds"))
+        .finish())
+}
+
+async fn update_request_headers(
+    session: Session,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    form: web::Form<UpdateRequestHeadersRequest>,
+) -> Result<HttpResponse> {
+    let user = require_auth(&session, &state).await?;
+    let subscription_id = path.into_inner();
+    
+    // Verify the subscription belongs to the user
+    if !db::user_owns_subscription(&state.db_pool, user.id, subscription_id).await
+        .map_err(|e| {
+            log::error!("Failed to check subscription ownership: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to check subscription ownership")
+        })? {
+        return Err(actix_web::error::ErrorForbidden("Access denied"));
+    }
+    
+    let headers = request_headers_from_form(&form.headers, &form.cookies)?;
+    
+    let feed_id = db::set_subscription_request_headers(&state.db_pool, subscription_id, &headers)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to update request headers: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to update request headers")
+        })?;
+    
+    refresh_changed_feed(&state, feed_id).await;
+    
+    Ok(HttpResponse::SeeOther()
+        .append_header(("Location", "/feeds"))
+        .finish())
+}
+
+async fn update_credentials(
+    session: Session,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    form: web::Form<UpdateCredentialsRequest>,
+) -> Result<HttpResponse> {
+    let user = require_auth(&session, &state).await?;
+    let subscription_id = path.into_inner();
+    
+    // Verify the subscription belongs to the user
+    if !db::user_owns_subscription(&state.db_pool, user.id, subscription_id).await
+        .map_err(|e| {
+            log::error!("Failed to check subscription ownership: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to check subscription ownership")
+        })? {
+        return Err(actix_web::error::ErrorForbidden("Access denied"));
+    }
+    
+    let username = form.username.trim();
+    let sealed = if username.is_empty() {
+        None
+    } else {
+        let credentials = BasicCredentials {
+            username: username.to_string(),
+            password: form.password.clone(),
+        };
+        Some(credentials.seal(&state.config.credentials_key)
+            .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?)
+    };
+    
+    let feed_id = db::set_subscription_credentials(&state.db_pool, subscription_id, sealed.as_ref())
+        .await
+        .map_err(|e| {
+            log::error!("Failed to update credentials: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to update credentials")
+        })?;
+    
+    refresh_changed_feed(&state, feed_id).await;
+    
+    Ok(HttpResponse::SeeOther()
+        .append_header(("Location", "/feeds"))
+        .finish())
+}
+
+/// Fetch a feed right after its request settings change, so the outcome
+/// shows up in its health; failures are recorded there rather than reported.
+async fn refresh_changed_feed(state: &AppState, feed_id: Uuid) {
+    match db::get_feed(&state.db_pool, feed_id).await {
+        // Static feeds only change when a new document is uploaded
+        Ok(Feed { kind: FeedKind::Static, .. }) => {}
+        Ok(feed) => {
+            if let Err(e) = rss_fetcher::refresh_feed(&state.db_pool, &state.http_client, &state.config, &feed).await {
+                log::warn!("{:#}", e);
+            }
+        }
+        Err(e) => log::error!("Failed to load feed: {}", e),
+    }
+}
+
+/// Combine the headers textarea and cookies field of a form into one list
+/// of request headers.
+fn request_headers_from_form(headers: &str, cookies: &str) -> Result<Vec<(String, String)>> {
+    let mut headers = http_client::parse_header_lines(headers)
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+    let cookies = cookies.trim();
+    if !cookies.is_empty() {
+        headers.retain(|(name, _)| name != "cookie");
+        headers.extend(http_client::parse_header_lines(&format!("Cookie: {}", cookies))
+            .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?);
+    }
+    Ok(headers)
+}
+
+async fn resume_feed(
+    session: Session,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse> {
+    let user = require_auth(&session, &state).await?;
+    let subscription_id = path.into_inner();
+    
+    // Verify the subscription belongs to the user
+    if !db::user_owns_subscription(&state.db_pool, user.id, subscription_id).await
+        .map_err(|e| {
+            log::error!("Failed to check subscription ownership: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to check subscription ownership")
+        })? {
+        return Err(actix_web::error::ErrorForbidden("Access denied"));
+    }
+    
+    db::resume_subscription_feed(&state.db_pool, subscription_id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to resume feed: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to resume feed")
+        })?;
+    
+    Ok(HttpResponse::SeeOther()
+        .append_header(("Location", "/feeds"))
+        .finish())
+}
+
 async fn unsubscribe(
     session: Session,
     state: web::Data<AppState>,
@@ -265,8 +784,9 @@ async fn delete_label(
 async fn refresh_feeds(session: Session, state: web::Data<AppState>) -> Result<HttpResponse> {
     let user = require_auth(&session, &state).await?;
     
-    // In production, this should be done in a background job
-    rss_fetcher::fetch_all_user_feeds(&state.db_pool, user.id)
+    // Feeds are also refreshed periodically by the background scheduler;
+    // this lets a user pull their own subscriptions immediately
+    rss_fetcher::fetch_all_user_feeds(&state.db_pool, &state.http_client, &state.config, user.id)
         .await
         .map_err(|e| {
             log::error!("Failed to refresh feeds: {}", e);
@@ -278,6 +798,118 @@ async fn refresh_feeds(session: Session, state: web::Data<AppState>) -> Result<H
         .finish())
 }
 
+async fn save_playback_position(
+    session: Session,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    json: web::Json<PlaybackPositionRequest>,
+) -> Result<HttpResponse> {
+    let user = require_auth(&session, &state).await?;
+    let item_id = path.into_inner();
+    
+    if !json.position_seconds.is_finite() || json.position_seconds < 0.0 {
+        return Err(actix_web::error::ErrorBadRequest("Invalid position"));
+    }
+    
+    let saved = db::set_playback_position(&state.db_pool, user.id, item_id, json.position_seconds)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to save playback position: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to save playback position")
+        })?;
+    
+    if !saved {
+        return Err(actix_web::error::ErrorNotFound("Item not found"));
+    }
+    
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true
+    })))
+}
+
+/// Verification of intent from a WebSub hub. Not behind login: hubs call it.
+async fn websub_verify(
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<WebSubVerification>,
+) -> Result<HttpResponse> {
+    let feed_id = path.into_inner();
+    
+    let challenge = websub::verify_intent(&state.db_pool, feed_id, &query)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to verify WebSub intent: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to verify subscription")
+        })?;
+    
+    match challenge {
+        Some(challenge) => Ok(HttpResponse::Ok().content_type("text/plain").body(challenge)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+/// Content pushed by a WebSub hub.
+async fn websub_deliver(
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    req: HttpRequest,
+    payload: web::Payload,
+) -> Result<HttpResponse> {
+    let feed_id = path.into_inner();
+    
+    let body = payload.to_bytes_limited(state.config.max_feed_bytes)
+        .await
+        .map_err(|_| actix_web::error::ErrorPayloadTooLarge("Payload too large"))??;
+    let header = |name| req.headers().get(name).and_then(|v| v.to_str().ok());
+    
+    let subscribed = websub::receive_push(
+        &state.db_pool,
+        feed_id,
+        header("X-Hub-Signature"),
+        header("Content-Type"),
+        &body,
+    )
+    .await
+    .map_err(|e| {
+        log::error!("Failed to store WebSub delivery: {:#}", e);
+        actix_web::error::ErrorInternalServerError("Failed to store delivery")
+    })?;
+    
+    if subscribed {
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        // Tells the hub to stop delivering
+        Ok(HttpResponse::Gone().finish())
+    }
+}
+
+async fn item_revisions(
+    session: Session,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse> {
+    let user = require_auth(&session, &state).await?;
+    let item_id = path.into_inner();
+    
+    let item = db::get_user_item(&state.db_pool, user.id, item_id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to load item: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to load item")
+        })?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Item not found"))?;
+    
+    let item_revisions = db::get_item_revisions(&state.db_pool, item_id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to load item revisions: {}", e);
+            actix_web::error::ErrorInternalServerError("Failed to load item revisions")
+        })?;
+    
+    let html: Markup = templates::item_revisions_page(&user.username, &item, &revisions::history(&item, &item_revisions));
+    Ok(HttpResponse::Ok().content_type("text/html").body(html.into_string()))
+}
+
 async fn mark_read(
     session: Session,
     state: web::Data<AppState>,
@@ -0,0 +1,438 @@
+use crate::{config::Config, local_feeds};
+use anyhow::{Context, Result};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, LOCATION};
+use reqwest::StatusCode;
+use std::error::Error as _;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use url::{Host, Url};
+
+/// Redirect hops a single fetch will follow before giving up.
+const MAX_REDIRECTS: usize = 10;
+
+/// A fetch was refused because the target resolves to an address users may
+/// not reach through this server.
+#[derive(Debug, Error)]
+#[error("{host} resolves to a private or reserved address")]
+pub struct BlockedAddressError {
+    pub host: String,
+}
+
+/// Which destinations outbound fetches may connect to.
+///
+/// Loopback, link-local, multicast and other reserved addresses are always
+/// refused, private network ranges unless the LAN opt-in is set. Hosts on
+/// the allow list bypass the checks entirely.
+#[derive(Debug, Clone, Default)]
+pub struct AddressPolicy {
+    pub allow_private_networks: bool,
+    pub allowed_hosts: Vec<String>,
+}
+
+impl AddressPolicy {
+    fn allows_host(&self, host: &str) -> bool {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        self.allowed_hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(host))
+    }
+
+    fn allows_ip(&self, ip: IpAddr) -> bool {
+        match ip {
+            IpAddr::V4(ip) => self.allows_ipv4(ip),
+            IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+                Some(mapped) => self.allows_ipv4(mapped),
+                None => self.allows_ipv6(ip),
+            },
+        }
+    }
+
+    fn allows_ipv4(&self, ip: Ipv4Addr) -> bool {
+        let [a, b, ..] = ip.octets();
+        let reserved = ip.is_unspecified()
+            || ip.is_loopback()
+            || ip.is_link_local()
+            || ip.is_multicast()
+            || ip.is_broadcast()
+            || ip.is_documentation()
+            || a == 0
+            || a >= 240
+            // 198.18.0.0/15 is set aside for benchmarking
+            || (a == 198 && (b & 0xfe) == 18);
+        // 100.64.0.0/10 is carrier-grade NAT, private in all but name
+        let private = ip.is_private() || (a == 100 && (64..128).contains(&b));
+        !reserved && (!private || self.allow_private_networks)
+    }
+
+    fn allows_ipv6(&self, ip: Ipv6Addr) -> bool {
+        if let Some(embedded) = embedded_ipv4(ip) {
+            return self.allows_ipv4(embedded);
+        }
+        let first = ip.segments()[0];
+        let reserved = ip.is_unspecified()
+            || ip.is_loopback()
+            || ip.is_multicast()
+            // Link-local fe80::/10 and the deprecated site-local fec0::/10
+            || (first & 0xffc0) == 0xfe80
+            || (first & 0xffc0) == 0xfec0;
+        let unique_local = (first & 0xfe00) == 0xfc00;
+        !reserved && (!unique_local || self.allow_private_networks)
+    }
+
+    /// Refuse URLs whose host is an IP literal outside the policy. Hostnames
+    /// are checked when they are resolved, so a DNS answer cannot slip past.
+    fn check_url(&self, url: &Url) -> Result<()> {
+        let ip = match url.host() {
+            Some(Host::Ipv4(ip)) => IpAddr::V4(ip),
+            Some(Host::Ipv6(ip)) => IpAddr::V6(ip),
+            _ => return Ok(()),
+        };
+        let host = url.host_str().unwrap_or_default();
+        if !self.allows_host(host) && !self.allows_ip(ip) {
+            return Err(BlockedAddressError { host: host.to_string() }.into());
+        }
+        Ok(())
+    }
+
+    /// Resolve a URL's hostname up front and refuse it if any address is
+    /// outside the policy. Used when a proxy does the real resolution.
+    ///
+    /// This is a best effort: the proxy looks the name up again, and a
+    /// hostile DNS server can answer it differently (DNS rebinding). Behind
+    /// a proxy, the proxy's own rules are what actually keep fetches off
+    /// private networks; configure it to refuse them.
+    async fn check_resolved(&self, url: &Url) -> Result<()> {
+        let Some(Host::Domain(host)) = url.host() else {
+            return Ok(());
+        };
+        if self.allows_host(host) {
+            return Ok(());
+        }
+        let port = url.port_or_known_default().unwrap_or(80);
+        let mut addrs = tokio::net::lookup_host((host, port)).await
+            .with_context(|| format!("Failed to resolve {}", host))?;
+        if addrs.any(|addr| !self.allows_ip(addr.ip())) {
+            return Err(BlockedAddressError { host: host.to_string() }.into());
+        }
+        Ok(())
+    }
+}
+
+/// The IPv4 address an IPv6 address reaches through a translation or
+/// tunnelling scheme: NAT64 `64:ff9b::/96`, 6to4 `2002::/16` and the
+/// deprecated IPv4-compatible `::a.b.c.d`.
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    let octets = ip.octets();
+    match segments {
+        [0x64, 0xff9b, 0, 0, 0, 0, ..] => Some(Ipv4Addr::new(octets[12], octets[13], octets[14], octets[15])),
+        [0x2002, ..] => Some(Ipv4Addr::new(octets[2], octets[3], octets[4], octets[5])),
+        // `::` and `::1` are the unspecified and loopback addresses, not IPv4
+        [0, 0, 0, 0, 0, 0, high, low] if high != 0 || low > 1 => {
+            Some(Ipv4Addr::new(octets[12], octets[13], octets[14], octets[15]))
+        }
+        _ => None,
+    }
+}
+
+/// DNS resolver that drops addresses the policy does not allow. The
+/// configured proxy is exempt: it is often on the local network, and
+/// reaching it is the point.
+struct GuardedResolver {
+    policy: Arc<AddressPolicy>,
+    proxy_host: Option<String>,
+}
+
+impl Resolve for GuardedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let policy = self.policy.clone();
+        let is_proxy = self.proxy_host.as_deref().is_some_and(|proxy| proxy.eq_ignore_ascii_case(name.as_str()));
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if is_proxy || policy.allows_host(&host) {
+                return Ok(Box::new(addrs.into_iter()) as Addrs);
+            }
+
+            let allowed: Vec<SocketAddr> = addrs.into_iter().filter(|addr| policy.allows_ip(addr.ip())).collect();
+            if allowed.is_empty() {
+                return Err(BlockedAddressError { host }.into());
+            }
+            Ok(Box::new(allowed.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// The HTTP client shared by every outbound fetch, so connections to the
+/// same host are pooled, with the timeouts, size limit and address policy
+/// from the config applied.
+#[derive(Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    policy: Arc<AddressPolicy>,
+    max_body_bytes: usize,
+    proxied: bool,
+    file_roots: Arc<Vec<PathBuf>>,
+}
+
+impl HttpClient {
+    pub fn new(config: &Config) -> Result<Self> {
+        let policy = Arc::new(AddressPolicy {
+            allow_private_networks: config.fetch_allow_private_networks,
+            allowed_hosts: config.fetch_allowed_hosts.clone(),
+        });
+
+        let mut builder = reqwest::Client::builder()
+            .user_agent(&config.fetch_user_agent)
+            .connect_timeout(Duration::from_secs(config.fetch_connect_timeout_secs))
+            .read_timeout(Duration::from_secs(config.fetch_read_timeout_secs))
+            .timeout(Duration::from_secs(config.fetch_timeout_secs))
+            // Redirects are followed in `get`, which checks each hop and needs
+            // to see them to notice permanent moves
+            .redirect(reqwest::redirect::Policy::none())
+            // Only the configured proxy, never one picked up from the environment
+            .no_proxy();
+
+        let mut proxy_host = None;
+        if let Some(proxy) = &config.fetch_proxy {
+            let proxy_url = Url::parse(proxy).with_context(|| format!("Invalid proxy URL {}", proxy))?;
+            proxy_host = proxy_url.host_str().map(|host| host.trim_start_matches('[').trim_end_matches(']').to_string());
+            builder = builder.proxy(reqwest::Proxy::all(proxy_url).context("Invalid proxy URL")?);
+        }
+
+        let client = builder
+            .dns_resolver(Arc::new(GuardedResolver { policy: policy.clone(), proxy_host }))
+            .build()
+            .context("Failed to build HTTP client")?;
+
+        Ok(Self {
+            client,
+            policy,
+            max_body_bytes: config.max_feed_bytes,
+            proxied: config.fetch_proxy.is_some(),
+            file_roots: Arc::new(local_feeds::allowed_roots(config)),
+        })
+    }
+
+    /// GET `url`, following redirects by hand. Alongside the final response,
+    /// returns the last URL reached through an unbroken run of permanent
+    /// (301/308) redirects from the start; a temporary redirect anywhere
+    /// before it means the original URL is still the one to keep.
+    ///
+    /// `headers` go with every request; `private_headers` carry credentials
+    /// and are only sent while the request stays on the original origin.
+    pub async fn get(
+        &self,
+        url: &str,
+        headers: &HeaderMap,
+        private_headers: &HeaderMap,
+    ) -> Result<(reqwest::Response, Option<String>)> {
+        let original = Url::parse(url).with_context(|| format!("Invalid URL {}", url))?;
+        let mut current = original.clone();
+        let mut moved_to = None;
+        let mut permanent = true;
+
+        for _ in 0..=MAX_REDIRECTS {
+            self.check_destination(&current).await?;
+
+            let mut request = self.client.get(current.clone()).headers(headers.clone());
+            if current.origin() == original.origin() {
+                request = request.headers(private_headers.clone());
+            }
+
+            let response = request.send().await.map_err(send_error)?;
+            let status = response.status();
+            if !status.is_redirection() || status == StatusCode::NOT_MODIFIED {
+                return Ok((response, moved_to));
+            }
+            let Some(location) = response.headers().get(LOCATION).and_then(|v| v.to_str().ok()) else {
+                return Ok((response, moved_to));
+            };
+
+            let next = current.join(location)
+                .with_context(|| format!("Invalid redirect from {} to {}", current, location))?;
+            permanent &= matches!(status, StatusCode::MOVED_PERMANENTLY | StatusCode::PERMANENT_REDIRECT);
+            if permanent {
+                moved_to = Some(next.to_string());
+            }
+            current = next;
+        }
+
+        anyhow::bail!("Too many redirects fetching {}", url)
+    }
+
+    /// POST a form to `url`, e.g. a subscription request to a WebSub hub.
+    /// Redirects are not followed.
+    pub async fn post_form(&self, url: &str, form: &[(&str, &str)]) -> Result<reqwest::Response> {
+        let url = Url::parse(url).with_context(|| format!("Invalid URL {}", url))?;
+        self.check_destination(&url).await?;
+        self.client.post(url).form(form).send().await.map_err(send_error)
+    }
+
+    async fn check_destination(&self, url: &Url) -> Result<()> {
+        if !matches!(url.scheme(), "http" | "https") {
+            anyhow::bail!("Unsupported URL scheme in {}", url);
+        }
+        self.policy.check_url(url)?;
+        if self.proxied {
+            self.policy.check_resolved(url).await?;
+        }
+        Ok(())
+    }
+
+    /// Read a response body, giving up as soon as it grows past the
+    /// configured maximum rather than buffering it all first.
+    pub async fn read_body(&self, mut response: reqwest::Response) -> Result<Vec<u8>> {
+        let limit = self.max_body_bytes;
+        if response.content_length().is_some_and(|length| length > limit as u64) {
+            anyhow::bail!("Response from {} is larger than {} bytes", response.url(), limit);
+        }
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > limit {
+                anyhow::bail!("Response from {} is larger than {} bytes", response.url(), limit);
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+
+    /// Read the file behind a `file://` feed URL, which must be inside one of
+    /// the configured feed directories, under the same size limit as a
+    /// response body. Returns the contents with the file's modification
+    /// stamp, or `None` when the stamp still equals `last_modified`.
+    pub async fn read_file(&self, url: &str, last_modified: Option<&str>) -> Result<Option<(Vec<u8>, String)>> {
+        let path = local_feeds::resolve_file_url(url, &self.file_roots).await?;
+        let metadata = tokio::fs::metadata(&path).await?;
+        let modified = local_feeds::modified_stamp(metadata.modified()?, metadata.len());
+        if last_modified == Some(modified.as_str()) {
+            return Ok(None);
+        }
+        if metadata.len() > self.max_body_bytes as u64 {
+            anyhow::bail!("{} is larger than {} bytes", path.display(), self.max_body_bytes);
+        }
+
+        let content = tokio::fs::read(&path).await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(Some((content, modified)))
+    }
+}
+
+/// Report a hostname refused by the resolver as such rather than as a generic
+/// connection failure.
+fn send_error(e: reqwest::Error) -> anyhow::Error {
+    let blocked = std::iter::successors(e.source(), |cause| (*cause).source())
+        .find_map(|cause| cause.downcast_ref::<BlockedAddressError>());
+    match blocked {
+        Some(blocked) => anyhow::Error::new(BlockedAddressError { host: blocked.host.clone() }),
+        None => e.into(),
+    }
+}
+
+/// Parse `Name: Value` lines, as typed into the subscription settings form,
+/// into request headers. Headers that would interfere with the request
+/// itself are refused.
+pub fn parse_header_lines(text: &str) -> Result<Vec<(String, String)>> {
+    const RESERVED: [&str; 5] = ["host", "content-length", "transfer-encoding", "connection", "upgrade"];
+
+    let mut headers = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let (name, value) = line.split_once(':')
+            .with_context(|| format!("Expected `Name: Value`, got {:?}", line))?;
+        let name = HeaderName::from_bytes(name.trim().as_bytes())
+            .with_context(|| format!("Invalid header name {:?}", name.trim()))?;
+        let value = value.trim();
+        HeaderValue::from_str(value).with_context(|| format!("Invalid value for header {}", name))?;
+        if RESERVED.contains(&name.as_str()) {
+            anyhow::bail!("The {} header cannot be overridden", name);
+        }
+        headers.push((name.to_string(), value.to_string()));
+    }
+    Ok(headers)
+}
+
+/// Build a header map from stored name/value pairs, skipping any that no
+/// longer parse.
+pub fn header_map(headers: &[(String, String)]) -> HeaderMap {
+    headers
+        .iter()
+        .filter_map(|(name, value)| {
+            Some((HeaderName::from_bytes(name.as_bytes()).ok()?, HeaderValue::from_str(value).ok()?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(policy: &AddressPolicy, ip: &str) -> bool {
+        policy.allows_ip(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn blocks_reserved_ipv4() {
+        let policy = AddressPolicy::default();
+        for ip in [
+            "0.0.0.0", "0.1.2.3", "127.0.0.1", "169.254.169.254", "224.0.0.1", "255.255.255.255", "192.0.2.1",
+            "198.51.100.1", "203.0.113.1", "198.18.0.1", "198.19.255.255", "240.0.0.1",
+        ] {
+            assert!(!allowed(&policy, ip), "{} should be blocked", ip);
+        }
+        for ip in ["10.0.0.1", "172.16.0.1", "192.168.1.1", "100.64.0.1"] {
+            assert!(!allowed(&policy, ip), "{} should be blocked", ip);
+        }
+        for ip in ["93.184.216.34", "198.17.255.255", "198.20.0.1", "100.128.0.1", "172.32.0.1"] {
+            assert!(allowed(&policy, ip), "{} should be allowed", ip);
+        }
+    }
+
+    #[test]
+    fn blocks_reserved_ipv6() {
+        let policy = AddressPolicy::default();
+        for ip in [
+            "::", "::1", "ff02::1", "fe80::1", "febf::1", "fec0::1", "feff::1", "fd00::1", "fc00::1",
+            // IPv4 behind mapping, NAT64, 6to4 and the IPv4-compatible form
+            "::ffff:127.0.0.1", "64:ff9b::a9fe:a9fe", "64:ff9b::10.0.0.1", "2002:7f00:1::", "2002:c0a8:101::1",
+            "::127.0.0.1", "::10.1.2.3",
+        ] {
+            assert!(!allowed(&policy, ip), "{} should be blocked", ip);
+        }
+        for ip in ["2606:4700::1111", "::ffff:93.184.216.34", "64:ff9b::5db8:d822", "2002:5db8:d822::1"] {
+            assert!(allowed(&policy, ip), "{} should be allowed", ip);
+        }
+    }
+
+    #[test]
+    fn private_network_opt_in() {
+        let policy = AddressPolicy { allow_private_networks: true, ..Default::default() };
+        for ip in ["10.0.0.1", "192.168.1.1", "100.64.0.1", "fd00::1", "64:ff9b::192.168.1.1", "2002:0a00:1::"] {
+            assert!(allowed(&policy, ip), "{} should be allowed", ip);
+        }
+        for ip in ["127.0.0.1", "169.254.169.254", "::1", "fe80::1", "fec0::1", "::ffff:127.0.0.1", "198.18.0.1"] {
+            assert!(!allowed(&policy, ip), "{} should still be blocked", ip);
+        }
+    }
+
+    #[test]
+    fn checks_ip_literal_urls() {
+        let policy = AddressPolicy {
+            allowed_hosts: vec!["192.168.1.5".to_string(), "::1".to_string()],
+            ..Default::default()
+        };
+        let check = |url: &str| policy.check_url(&Url::parse(url).unwrap());
+
+        assert!(check("http://127.0.0.1/feed").unwrap_err().downcast_ref::<BlockedAddressError>().is_some());
+        assert!(check("http://[fe80::1]/feed").is_err());
+        assert!(check("http://[::ffff:10.0.0.1]/feed").is_err());
+        assert!(check("http://192.168.1.6/feed").is_err());
+        assert!(check("http://192.168.1.5:8080/feed").is_ok());
+        assert!(check("http://[::1]/feed").is_ok());
+        assert!(check("https://93.184.216.34/feed").is_ok());
+        // Hostnames wait for their DNS answer
+        assert!(check("http://localhost/feed").is_ok());
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{config::Config, db, rss_fetcher, AppState};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use time::OffsetDateTime;
+use tokio::time::MissedTickBehavior;
+use url::Url;
+
+/// Extensions of the files picked up from the watched directory.
+const FEED_FILE_EXTENSIONS: [&str; 2] = ["xml", "json"];
+
+/// Directories `file://` feed URLs may point into: the configured feed
+/// directories and the watched directory, with symlinks resolved. Ones that
+/// don't exist are skipped.
+pub fn allowed_roots(config: &Config) -> Vec<PathBuf> {
+    config.feed_directories
+        .iter()
+        .chain(config.watch_directory.as_ref())
+        .filter_map(|dir| match std::fs::canonicalize(dir) {
+            Ok(dir) => Some(dir),
+            Err(e) => {
+                log::warn!("Ignoring feed directory {}: {}", dir.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Map a `file://` URL to the file it names, refusing anything that ends up
+/// outside `roots` once `..` and symlinks are resolved.
+pub async fn resolve_file_url(url: &str, roots: &[PathBuf]) -> Result<PathBuf> {
+    let parsed = Url::parse(url).with_context(|| format!("Invalid URL {}", url))?;
+    let path = parsed.to_file_path()
+        .map_err(|_| anyhow::anyhow!("Not a local file URL: {}", url))?;
+    let path = tokio::fs::canonicalize(&path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+
+    if !roots.iter().any(|root| path.starts_with(root)) {
+        anyhow::bail!("{} is not inside a configured feed directory", path.display());
+    }
+    Ok(path)
+}
+
+/// A file's modification time, to the nanosecond, and length, in the form
+/// stored as a feed's `Last-Modified` validator, so unchanged files are
+/// skipped like a 304. An HTTP date would only resolve whole seconds and
+/// miss a file rewritten twice within one.
+pub fn modified_stamp(modified: SystemTime, length: u64) -> String {
+    format!("{}:{}", OffsetDateTime::from(modified).unix_timestamp_nanos(), length)
+}
+
+/// `file://` URL prefix shared by every feed ingested from the watched
+/// directory.
+pub fn watch_url_prefix(config: &Config) -> Option<String> {
+    let dir = std::fs::canonicalize(config.watch_directory.as_ref()?).ok()?;
+    Url::from_directory_path(dir).ok().map(String::from)
+}
+
+/// Spawn the worker that ingests feed files dropped into the watched
+/// directory and refreshes them whenever they change.
+///
+/// The directory is polled rather than subscribed to, so it works the same
+/// on network filesystems.
+pub fn spawn(state: AppState) {
+    let Some(dir) = state.config.watch_directory.clone() else {
+        return;
+    };
+    log::info!("Watching {} for feed files", dir.display());
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(state.config.watch_interval_secs.max(1)));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut seen: HashMap<PathBuf, String> = HashMap::new();
+
+        loop {
+            interval.tick().await;
+            if let Err(e) = scan(&state, &dir, &mut seen).await {
+                log::error!("Failed to scan {}: {:#}", dir.display(), e);
+            }
+        }
+    });
+}
+
+async fn scan(state: &AppState, dir: &Path, seen: &mut HashMap<PathBuf, String>) -> Result<()> {
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let is_feed_file = path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| FEED_FILE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+        if !is_feed_file {
+            continue;
+        }
+
+        let metadata = entry.metadata().await?;
+        let Ok(modified) = metadata.modified() else {
+            continue;
+        };
+        let stamp = modified_stamp(modified, metadata.len());
+        if !metadata.is_file() || seen.get(&path) == Some(&stamp) {
+            continue;
+        }
+
+        match ingest(state, &path).await {
+            Ok(()) => {
+                seen.insert(path, stamp);
+            }
+            // Left out of `seen` so a half-written file is retried next pass
+            Err(e) => log::warn!("Failed to ingest {}: {:#}", path.display(), e),
+        }
+    }
+    Ok(())
+}
+
+async fn ingest(state: &AppState, path: &Path) -> Result<()> {
+    let path = tokio::fs::canonicalize(path).await?;
+    let url = Url::from_file_path(&path)
+        .map_err(|_| anyhow::anyhow!("Cannot build a URL for {}", path.display()))?;
+
+    let feed = db::create_or_get_feed(&state.db_pool, url.as_str()).await?;
+    rss_fetcher::refresh_feed(&state.db_pool, &state.http_client, &state.config, &feed).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamps_tell_close_writes_apart() {
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_nanos(1_700_000_000_123_456_789);
+        assert_eq!(modified_stamp(modified, 42), "1700000000123456789:42");
+        assert_ne!(modified_stamp(modified, 42), modified_stamp(modified + Duration::from_millis(10), 42));
+        assert_ne!(modified_stamp(modified, 42), modified_stamp(modified, 43));
+    }
+
+    #[actix_web::test]
+    async fn keeps_file_urls_inside_roots() {
+        let base = std::env::temp_dir().join(format!("myrss-local-feeds-{}", std::process::id()));
+        let root = base.join("feeds");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("inside.xml"), "<rss/>").unwrap();
+        std::fs::write(base.join("secret.xml"), "<rss/>").unwrap();
+        std::os::unix::fs::symlink(base.join("secret.xml"), root.join("link.xml")).unwrap();
+        let roots = vec![std::fs::canonicalize(&root).unwrap()];
+        let url = |path: &Path| format!("file://{}", path.display());
+
+        let resolved = resolve_file_url(&url(&root.join("inside.xml")), &roots).await.unwrap();
+        assert_eq!(resolved, roots[0].join("inside.xml"));
+
+        assert!(resolve_file_url(&url(&root.join("../secret.xml")), &roots).await.is_err());
+        assert!(resolve_file_url(&format!("{}/%2E%2E/secret.xml", url(&root)), &roots).await.is_err());
+        assert!(resolve_file_url(&url(&root.join("link.xml")), &roots).await.is_err());
+        assert!(resolve_file_url(&url(&root.join("missing.xml")), &roots).await.is_err());
+        assert!(resolve_file_url("https://example.com/feed.xml", &roots).await.is_err());
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -1,10 +1,23 @@
+mod article;
 mod auth;
 mod config;
+mod credentials;
+mod dates;
 mod db;
+mod discovery;
+mod encoding;
 mod handlers;
+mod http_client;
+mod local_feeds;
+mod media;
 mod models;
+mod revisions;
 mod rss_fetcher;
+mod sanitize;
+mod scheduler;
+mod scrape;
 mod templates;
+mod websub;
 
 use actix_files::Files;
 use actix_session::{SessionMiddleware, storage::CookieSessionStore};
@@ -18,6 +31,7 @@ use std::sync::Arc;
 pub struct AppState {
     pub db_pool: sqlx::PgPool,
     pub config: Arc<config::Config>,
+    pub http_client: http_client::HttpClient,
 }
 
 #[actix_web::main]
@@ -36,11 +50,18 @@ async fn main() -> Result<()> {
         .run(&db_pool)
         .await?;
 
+    // Pages show stored item HTML as is, so older items are cleaned first
+    sanitize::backfill(&db_pool).await?;
+
     let state = AppState {
         db_pool,
         config: Arc::new(config.clone()),
+        http_client: http_client::HttpClient::new(&config)?,
     };
 
+    scheduler::spawn(state.clone());
+    local_feeds::spawn(state.clone());
+
     log::info!("Starting server at http://{}:{}", config.host, config.port);
 
     HttpServer::new(move || {
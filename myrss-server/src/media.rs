@@ -0,0 +1,231 @@
+use crate::models::NewEnclosure;
+use scraper::{Html, Selector};
+use std::collections::BTreeMap;
+use url::Url;
+
+const MEDIA_RSS_NAMESPACE: &str = "http://search.yahoo.com/mrss/";
+
+/// What an item's Media RSS elements (`media:thumbnail`, `media:content`,
+/// and the same wrapped in `media:group`) contribute.
+#[derive(Debug, Default)]
+pub struct MediaRss {
+    pub thumbnail: Option<String>,
+    pub content: Vec<NewEnclosure>,
+}
+
+/// The parts of an extension element the Media RSS walk needs, implemented
+/// for both the `rss` and `atom_syndication` flavours.
+trait MediaElement: Sized {
+    fn attr(&self, name: &str) -> Option<&str>;
+    fn children(&self, name: &str) -> &[Self];
+    fn text(&self) -> Option<&str>;
+}
+
+macro_rules! impl_media_element {
+    ($extension:ty) => {
+        impl MediaElement for $extension {
+            fn attr(&self, name: &str) -> Option<&str> {
+                self.attrs.get(name).map(String::as_str).filter(|value| !value.trim().is_empty())
+            }
+
+            fn children(&self, name: &str) -> &[Self] {
+                self.children.get(name).map(Vec::as_slice).unwrap_or_default()
+            }
+
+            fn text(&self) -> Option<&str> {
+                self.value.as_deref().map(str::trim).filter(|value| !value.is_empty())
+            }
+        }
+    };
+}
+
+impl_media_element!(rss::extension::Extension);
+impl_media_element!(atom_syndication::extension::Extension);
+
+/// Media RSS from an RSS item's extensions.
+pub fn from_rss_extensions(extensions: &rss::extension::ExtensionMap, namespaces: &BTreeMap<String, String>) -> MediaRss {
+    from_extensions(extensions, namespaces)
+}
+
+/// Media RSS from an Atom entry's extensions, as used by YouTube.
+pub fn from_atom_extensions(
+    extensions: &atom_syndication::extension::ExtensionMap,
+    namespaces: &BTreeMap<String, String>,
+) -> MediaRss {
+    from_extensions(extensions, namespaces)
+}
+
+fn from_extensions<E: MediaElement>(
+    extensions: &BTreeMap<String, BTreeMap<String, Vec<E>>>,
+    namespaces: &BTreeMap<String, String>,
+) -> MediaRss {
+    let mut media = MediaRss::default();
+    // Fall back to the conventional prefix when the namespace was declared
+    // somewhere the parser does not look
+    let prefixes = namespaces
+        .iter()
+        .filter(|(_, uri)| *uri == MEDIA_RSS_NAMESPACE)
+        .map(|(prefix, _)| prefix.as_str())
+        .chain(std::iter::once("media"));
+
+    for prefix in prefixes {
+        if let Some(elements) = extensions.get(prefix) {
+            let children = |name: &str| elements.get(name).map(Vec::as_slice).unwrap_or_default();
+            collect(&children, &mut media);
+            break;
+        }
+    }
+    media
+}
+
+/// Walk one level of Media RSS elements, descending into `media:group`s.
+fn collect<'a, E: MediaElement + 'a>(children: &dyn Fn(&str) -> &'a [E], media: &mut MediaRss) {
+    if media.thumbnail.is_none() {
+        media.thumbnail = children("thumbnail").iter().find_map(|thumbnail| thumbnail.attr("url")).map(str::to_string);
+    }
+
+    for content in children("content") {
+        let Some(url) = content.attr("url") else {
+            continue;
+        };
+        let mime_type = content.attr("type").map(str::to_string);
+        let is_image = content.attr("medium") == Some("image")
+            || mime_type.as_deref().is_some_and(|t| t.starts_with("image/"));
+
+        if media.thumbnail.is_none() {
+            media.thumbnail = content.children("thumbnail")
+                .iter()
+                .find_map(|thumbnail| thumbnail.attr("url"))
+                .or(is_image.then_some(url))
+                .map(str::to_string);
+        }
+        if media.content.iter().any(|existing| existing.url == url) {
+            continue;
+        }
+        media.content.push(NewEnclosure {
+            url: url.to_string(),
+            mime_type,
+            length: content.attr("fileSize").and_then(|size| size.trim().parse().ok()),
+            title: content.children("title").iter().find_map(MediaElement::text).map(str::to_string),
+            duration_seconds: content.attr("duration").and_then(|secs| secs.trim().parse().ok()),
+        });
+    }
+
+    for group in children("group") {
+        collect(&|name: &str| group.children(name), media);
+    }
+}
+
+/// The first real image in an item's HTML, resolved against the item's
+/// link. Inline `data:` images and 1x1 tracking pixels are skipped.
+pub fn first_image(html: &str, base: Option<&str>) -> Option<String> {
+    let selector = Selector::parse("img[src]").ok()?;
+    let document = Html::parse_fragment(html);
+    let base = base.and_then(|base| Url::parse(base).ok());
+
+    document.select(&selector).find_map(|img| {
+        let element = img.value();
+        let is_pixel = ["width", "height"].iter().any(|dimension| element.attr(dimension).is_some_and(|v| v.trim() == "1"));
+        let src = element.attr("src")?.trim();
+        if is_pixel || src.is_empty() || src.starts_with("data:") {
+            return None;
+        }
+        match &base {
+            Some(base) => base.join(src).ok().map(String::from),
+            None => Url::parse(src).ok().map(String::from),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rss_media(xml: &str) -> MediaRss {
+        let channel = rss::Channel::read_from(xml.as_bytes()).unwrap();
+        from_rss_extensions(&channel.items[0].extensions, &channel.namespaces)
+    }
+
+    #[test]
+    fn reads_rss_media_content() {
+        let media = rss_media(r#"<rss version="2.0" xmlns:media="http://search.yahoo.com/mrss/"><channel><title>T</title><link>https://example.com/</link><description>D</description>
+            <item><title>Episode</title>
+                <media:content url="https://cdn.example/ep.mp3" type="audio/mpeg" fileSize="1024" duration="61">
+                    <media:title>Full episode</media:title>
+                </media:content>
+                <media:content url="https://cdn.example/ep.mp3" type="audio/mpeg"/>
+                <media:content url="https://cdn.example/cover.jpg" medium="image"/>
+                <media:content url=" "/>
+            </item></channel></rss>"#);
+
+        assert_eq!(media.thumbnail.as_deref(), Some("https://cdn.example/cover.jpg"));
+        assert_eq!(media.content.len(), 2);
+        let audio = &media.content[0];
+        assert_eq!(audio.url, "https://cdn.example/ep.mp3");
+        assert_eq!(audio.mime_type.as_deref(), Some("audio/mpeg"));
+        assert_eq!(audio.length, Some(1024));
+        assert_eq!(audio.duration_seconds, Some(61));
+        assert_eq!(audio.title.as_deref(), Some("Full episode"));
+    }
+
+    #[test]
+    fn descends_into_nested_groups() {
+        let media = rss_media(r#"<rss version="2.0" xmlns:media="http://search.yahoo.com/mrss/"><channel><title>T</title><link>https://example.com/</link><description>D</description>
+            <item><title>Video</title>
+                <media:group>
+                    <media:content url="https://cdn.example/720.mp4" type="video/mp4">
+                        <media:thumbnail url="https://cdn.example/720.jpg"/>
+                    </media:content>
+                    <media:group><media:content url="https://cdn.example/1080.mp4" type="video/mp4"/></media:group>
+                </media:group>
+            </item></channel></rss>"#);
+
+        assert_eq!(media.thumbnail.as_deref(), Some("https://cdn.example/720.jpg"));
+        let urls: Vec<_> = media.content.iter().map(|content| content.url.as_str()).collect();
+        assert_eq!(urls, ["https://cdn.example/720.mp4", "https://cdn.example/1080.mp4"]);
+    }
+
+    #[test]
+    fn follows_the_declared_prefix() {
+        let media = rss_media(r#"<rss version="2.0" xmlns:m="http://search.yahoo.com/mrss/" xmlns:media="https://other.example/ns"><channel><title>T</title><link>https://example.com/</link><description>D</description>
+            <item><title>Photo</title>
+                <media:thumbnail url="https://other.example/not-media-rss.jpg"/>
+                <m:thumbnail url="https://cdn.example/thumb.jpg"/>
+            </item></channel></rss>"#);
+        assert_eq!(media.thumbnail.as_deref(), Some("https://cdn.example/thumb.jpg"));
+    }
+
+    #[test]
+    fn reads_youtube_entries() {
+        let xml = r#"<feed xmlns="http://www.w3.org/2005/Atom" xmlns:yt="http://www.youtube.com/xml/schemas/2015" xmlns:media="http://search.yahoo.com/mrss/">
+            <title>Channel</title><id>yt:channel:UC1</id><updated>2024-01-01T00:00:00Z</updated>
+            <entry><id>yt:video:abc</id><title>A video</title><updated>2024-01-01T00:00:00Z</updated>
+                <yt:videoId>abc</yt:videoId>
+                <media:group>
+                    <media:title>A video</media:title>
+                    <media:content url="https://www.youtube.com/v/abc?version=3" type="application/x-shockwave-flash" width="640" height="390"/>
+                    <media:thumbnail url="https://i1.ytimg.com/vi/abc/hqdefault.jpg" width="480" height="360"/>
+                    <media:description>About the video</media:description>
+                </media:group>
+            </entry></feed>"#;
+        let feed = atom_syndication::Feed::read_from(xml.as_bytes()).unwrap();
+        let media = from_atom_extensions(&feed.entries[0].extensions, &feed.namespaces);
+
+        assert_eq!(media.thumbnail.as_deref(), Some("https://i1.ytimg.com/vi/abc/hqdefault.jpg"));
+        assert_eq!(media.content.len(), 1);
+        assert_eq!(media.content[0].url, "https://www.youtube.com/v/abc?version=3");
+        assert_eq!(media.content[0].mime_type.as_deref(), Some("application/x-shockwave-flash"));
+    }
+
+    #[test]
+    fn skips_inline_images_and_pixels() {
+        let html = r#"<p><img src="data:image/gif;base64,R0lGOD">
+            <img src="https://track.example/p.gif" width="1" height="1">
+            <img src=" " alt="empty">
+            <img src="/images/chart.png" width="640"></p>"#;
+        assert_eq!(first_image(html, Some("https://blog.example/posts/1")).as_deref(), Some("https://blog.example/images/chart.png"));
+        // A relative image without a base has nothing to resolve against
+        assert_eq!(first_image(html, None), None);
+        assert_eq!(first_image("<p>No images</p>", None), None);
+    }
+}
@@ -8,8 +8,6 @@ pub struct User {
     pub id: Uuid,
     pub username: String,
     pub email: String,
-    #[serde(skip)]
-    pub password_hash: String,
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: OffsetDateTime,
     #[serde(with = "time::serde::rfc3339")]
@@ -24,18 +22,47 @@ pub struct Feed {
     pub description: Option<String>,
     #[serde(with = "time::serde::rfc3339::option")]
     pub last_fetched: Option<OffsetDateTime>,
+    pub fetch_interval_secs: i32,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub next_fetch_at: Option<OffsetDateTime>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub last_error: Option<String>,
+    pub last_status: Option<i32>,
+    pub consecutive_failures: i32,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_success_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub paused_at: Option<OffsetDateTime>,
+    pub kind: FeedKind,
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: OffsetDateTime,
     #[serde(with = "time::serde::rfc3339")]
     pub updated_at: OffsetDateTime,
 }
 
+/// Where a feed's documents come from, stored in `feeds.kind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "varchar", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum FeedKind {
+    /// Fetched from its URL
+    Remote,
+    /// Pasted by a user and never fetched; it changes only when a new
+    /// document is uploaded
+    Static,
+    /// Built from a web page by applying its `ScrapeRules`
+    Scraped,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Subscription {
     pub id: Uuid,
     pub user_id: Uuid,
     pub feed_id: Uuid,
     pub custom_title: Option<String>,
+    pub refresh_interval_secs: Option<i32>,
+    pub fetch_full_content: bool,
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: OffsetDateTime,
 }
@@ -56,9 +83,72 @@ pub struct SubscriptionWithLabels {
     pub subscription: Subscription,
     pub feed_title: Option<String>,
     pub feed_url: String,
+    pub feed_kind: FeedKind,
+    /// Selectors of a feed scraped from a web page
+    pub scrape_rules: Option<ScrapeRules>,
+    pub fetch_status: FeedFetchStatus,
+    /// Names of the custom headers sent with this subscription's fetches;
+    /// the values are never read back out
+    pub request_header_names: Vec<String>,
+    /// Whether HTTP Basic credentials are stored; they are never read back out
+    pub has_credentials: bool,
     pub labels: Vec<Label>,
 }
 
+/// Outcome of recent fetches of a feed, shown to its subscribers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedFetchStatus {
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_fetched: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_success_at: Option<OffsetDateTime>,
+    pub last_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub consecutive_failures: i32,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub paused_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedHealth {
+    /// Not fetched successfully yet
+    Pending,
+    Healthy,
+    /// Recent fetches failed, but not enough to call the feed broken
+    Degraded,
+    Failing,
+    /// No longer fetched until a subscriber resumes it
+    Paused,
+}
+
+impl FeedFetchStatus {
+    /// Consecutive failures after which a feed is reported as failing.
+    pub const FAILING_THRESHOLD: i32 = 3;
+
+    pub fn health(&self) -> FeedHealth {
+        if self.paused_at.is_some() {
+            return FeedHealth::Paused;
+        }
+        match self.consecutive_failures {
+            0 if self.last_success_at.is_none() => FeedHealth::Pending,
+            0 => FeedHealth::Healthy,
+            n if n < Self::FAILING_THRESHOLD => FeedHealth::Degraded,
+            _ => FeedHealth::Failing,
+        }
+    }
+}
+
+/// A feed's WebSub subscription at its hub.
+#[derive(Clone, FromRow)]
+pub struct WebSubSubscription {
+    pub feed_id: Uuid,
+    pub hub_url: String,
+    pub topic_url: String,
+    /// HMAC key the hub signs pushed content with
+    pub secret: String,
+    pub renew_at: OffsetDateTime,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Item {
     pub id: Uuid,
@@ -71,8 +161,90 @@ pub struct Item {
     pub pub_date: Option<OffsetDateTime>,
     pub author: Option<String>,
     pub content: Option<String>,
+    pub duration_seconds: Option<i64>,
+    pub episode: Option<i32>,
+    pub season: Option<i32>,
+    pub image_url: Option<String>,
+    pub thumbnail_url: Option<String>,
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: OffsetDateTime,
+    /// When the feed last edited the entry; earlier versions are kept in
+    /// `item_revisions`
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub revised_at: Option<OffsetDateTime>,
+}
+
+/// An earlier version of an item, replaced at `created_at` by a feed edit.
+#[derive(Debug, Clone, FromRow)]
+pub struct ItemRevision {
+    pub title: String,
+    pub description: Option<String>,
+    pub content: Option<String>,
+    pub created_at: OffsetDateTime,
+}
+
+/// The stored HTML of an item, for cleaning items saved before ingest
+/// sanitized them.
+#[derive(Debug, FromRow)]
+pub struct ItemHtml {
+    pub id: Uuid,
+    pub link: Option<String>,
+    pub description: Option<String>,
+    pub content: Option<String>,
+}
+
+/// A feed entry normalized from any supported syndication format, ready to be
+/// written to the `items` table.
+#[derive(Debug, Clone, Default)]
+pub struct NewItem {
+    pub guid: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub link: Option<String>,
+    pub pub_date: Option<OffsetDateTime>,
+    pub author: Option<String>,
+    pub content: Option<String>,
+    pub enclosures: Vec<NewEnclosure>,
+    /// Podcast episode details from the iTunes extension
+    pub duration_seconds: Option<i64>,
+    pub episode: Option<i32>,
+    pub season: Option<i32>,
+    pub image_url: Option<String>,
+    /// Preview image from Media RSS or the item's HTML
+    pub thumbnail_url: Option<String>,
+}
+
+/// A media file attached to a feed entry (RSS `<enclosure>`, JSON Feed
+/// `attachments`).
+#[derive(Debug, Clone, Default)]
+pub struct NewEnclosure {
+    pub url: String,
+    pub mime_type: Option<String>,
+    pub length: Option<i64>,
+    pub title: Option<String>,
+    pub duration_seconds: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Enclosure {
+    pub id: Uuid,
+    pub item_id: Uuid,
+    pub url: String,
+    pub mime_type: Option<String>,
+    pub length: Option<i64>,
+    pub title: Option<String>,
+    pub duration_seconds: Option<i64>,
+}
+
+impl Enclosure {
+    /// Whether a browser can play this inline, and if so as `audio` or `video`.
+    pub fn media_kind(&self) -> Option<&'static str> {
+        match self.mime_type.as_deref()?.split('/').next()? {
+            "audio" => Some("audio"),
+            "video" => Some("video"),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -81,13 +253,43 @@ pub struct ItemWithReadStatus {
     pub item: Item,
     pub is_read: bool,
     pub feed_title: Option<String>,
+    pub enclosures: Vec<Enclosure>,
+    /// Seconds into the item's media the user had got to
+    pub playback_position: Option<f64>,
 }
 
-#[derive(Debug, Deserialize)]
+/// Not `Debug`: it can carry a password.
+#[derive(Deserialize)]
 pub struct AddFeedRequest {
     pub url: Option<String>,
     pub content: Option<String>,
     pub labels: Option<Vec<String>>,
+    /// Custom request headers, one `Name: Value` per line
+    pub headers: Option<String>,
+    pub cookies: Option<String>,
+    /// HTTP Basic credentials for a private feed
+    pub auth_username: Option<String>,
+    pub auth_password: Option<String>,
+}
+
+/// CSS selectors that turn a web page into a feed. Each match of
+/// `item_selector` is an item; the others are evaluated inside it.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ScrapeRules {
+    pub item_selector: String,
+    pub title_selector: String,
+    pub link_selector: Option<String>,
+    pub date_selector: Option<String>,
+    pub body_selector: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddScrapedFeedRequest {
+    pub url: String,
+    #[serde(flatten)]
+    pub rules: ScrapeRules,
+    pub headers: Option<String>,
+    pub cookies: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -95,6 +297,58 @@ pub struct UpdateFeedLabelsRequest {
     pub labels: Vec<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct UpdateRefreshIntervalRequest {
+    /// Seconds between fetches, or empty for automatic scheduling
+    pub refresh_interval_secs: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateFullContentRequest {
+    /// Present when the checkbox is ticked
+    pub fetch_full_content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRequestHeadersRequest {
+    /// One `Name: Value` header per line
+    pub headers: String,
+    pub cookies: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UploadStaticFeedRequest {
+    pub content: String,
+}
+
+/// Not `Debug`: it carries a password.
+#[derive(Deserialize)]
+pub struct UpdateCredentialsRequest {
+    /// Empty to remove the stored credentials
+    pub username: String,
+    pub password: String,
+}
+
+/// A hub's verification of intent, sent as query parameters to a callback.
+#[derive(Debug, Deserialize)]
+pub struct WebSubVerification {
+    #[serde(rename = "hub.mode")]
+    pub mode: String,
+    #[serde(rename = "hub.topic")]
+    pub topic: String,
+    #[serde(rename = "hub.challenge")]
+    pub challenge: Option<String>,
+    #[serde(rename = "hub.lease_seconds")]
+    pub lease_seconds: Option<i32>,
+    #[serde(rename = "hub.reason")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlaybackPositionRequest {
+    pub position_seconds: f64,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct MarkReadRequest {
     pub item_ids: Vec<Uuid>,
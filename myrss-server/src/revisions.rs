@@ -0,0 +1,180 @@
+use crate::models::{Item, ItemRevision};
+use scraper::{ElementRef, Html};
+use similar::{ChangeTag, TextDiff};
+use time::OffsetDateTime;
+
+/// A run of words in a diff that were kept, removed or added.
+#[derive(Debug, PartialEq)]
+pub struct DiffSpan {
+    pub tag: ChangeTag,
+    pub text: String,
+}
+
+/// What one edit of an item changed, field by field.
+pub struct Edit {
+    pub changed_at: OffsetDateTime,
+    pub fields: Vec<(&'static str, Vec<DiffSpan>)>,
+}
+
+/// Elements whose text is never shown. Items stored before sanitizing
+/// was added can still contain them.
+const HIDDEN_TAGS: [&str; 4] = ["script", "style", "noscript", "template"];
+
+/// The text a reader sees in a piece of feed HTML, with whitespace
+/// collapsed.
+pub fn visible_text(html: &str) -> String {
+    let fragment = Html::parse_fragment(html);
+    let words: Vec<&str> = fragment
+        .root_element()
+        .descendants()
+        .filter_map(|node| Some((node, node.value().as_text()?)))
+        .filter(|(node, _)| {
+            !node.ancestors().filter_map(ElementRef::wrap).any(|element| HIDDEN_TAGS.contains(&element.value().name()))
+        })
+        .flat_map(|(_, text)| text.split_whitespace())
+        .collect();
+    words.join(" ")
+}
+
+/// Whether a feed's new HTML reads differently from the old. Markup-only
+/// changes, such as reordered attributes or rewritten tracking links, are
+/// not edits.
+pub fn text_changed(old: Option<&str>, new: Option<&str>) -> bool {
+    let (old, new) = (old.unwrap_or_default(), new.unwrap_or_default());
+    // Most refreshes repeat the item unchanged, and then there is no need
+    // to parse anything
+    old != new && visible_text(old) != visible_text(new)
+}
+
+/// Every edit of an item, newest first, comparing each stored revision with
+/// the version that replaced it.
+pub fn history(item: &Item, revisions: &[ItemRevision]) -> Vec<Edit> {
+    let current = (item.title.as_str(), item.description.as_deref(), item.content.as_deref());
+    let versions: Vec<_> = revisions
+        .iter()
+        .map(|revision| (revision.title.as_str(), revision.description.as_deref(), revision.content.as_deref()))
+        .chain(std::iter::once(current))
+        .collect();
+
+    let mut edits: Vec<Edit> = revisions
+        .iter()
+        .zip(versions.windows(2))
+        .map(|(revision, pair)| {
+            let (old, new) = (pair[0], pair[1]);
+            let fields = [
+                ("Title", old.0.to_string(), new.0.to_string()),
+                ("Description", visible_text(old.1.unwrap_or_default()), visible_text(new.1.unwrap_or_default())),
+                ("Content", visible_text(old.2.unwrap_or_default()), visible_text(new.2.unwrap_or_default())),
+            ];
+            Edit {
+                changed_at: revision.created_at,
+                fields: fields
+                    .into_iter()
+                    .filter(|(_, old, new)| old.trim() != new.trim())
+                    .map(|(name, old, new)| (name, diff_words(&old, &new)))
+                    .collect(),
+            }
+        })
+        .collect();
+    edits.reverse();
+    edits
+}
+
+/// Word-level diff of two texts, with neighbouring words of the same kind
+/// merged into one span.
+pub fn diff_words(old: &str, new: &str) -> Vec<DiffSpan> {
+    let mut spans: Vec<DiffSpan> = Vec::new();
+    for change in TextDiff::from_words(old, new).iter_all_changes() {
+        match spans.last_mut() {
+            Some(span) if span.tag == change.tag() => span.text.push_str(change.value()),
+            _ => spans.push(DiffSpan {
+                tag: change.tag(),
+                text: change.value().to_string(),
+            }),
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignores_markup_changes() {
+        assert!(!text_changed(Some(r#"<p class="a">Hello  <b>world</b></p>"#), Some("<p>Hello <strong>world</strong></p>")));
+        assert!(!text_changed(None, Some("<p> </p>")));
+        assert!(text_changed(Some("<p>Hello world</p>"), Some("<p>Hello there</p>")));
+    }
+
+    #[test]
+    fn ignores_scripts_and_styles() {
+        assert_eq!(visible_text("<p>Hello <script>track(1)</script><b>world</b></p><style>p { }</style>"), "Hello world");
+        assert!(!text_changed(Some("<p>Hi</p><script>track(1)</script>"), Some("<p>Hi</p><script>track(2)</script>")));
+        assert!(!text_changed(Some("<p>Hi</p><noscript><img src=pixel></noscript>"), Some("<p>Hi</p>")));
+    }
+
+    #[test]
+    fn lists_edits_newest_first() {
+        let at = |timestamp| OffsetDateTime::from_unix_timestamp(timestamp).unwrap();
+        let revision = |title: &str, description: &str, content: Option<&str>, created_at| ItemRevision {
+            title: title.to_string(),
+            description: Some(description.to_string()),
+            content: content.map(str::to_string),
+            created_at,
+        };
+        let revisions = [
+            revision("Launch day", "<p>We launch today.</p>", None, at(1_700_000_000)),
+            revision("Launch day", "<p>We launched today.</p>", Some("<p>Details</p>"), at(1_700_003_600)),
+        ];
+        let item = Item {
+            id: uuid::Uuid::nil(),
+            feed_id: uuid::Uuid::nil(),
+            guid: "1".to_string(),
+            title: "Launch delayed".to_string(),
+            description: Some(r#"<p class="x">We launched today.</p>"#.to_string()),
+            link: None,
+            pub_date: None,
+            author: None,
+            content: Some("<p>Details</p>".to_string()),
+            duration_seconds: None,
+            episode: None,
+            season: None,
+            image_url: None,
+            thumbnail_url: None,
+            created_at: at(1_699_990_000),
+            revised_at: Some(at(1_700_003_600)),
+        };
+
+        let edits = history(&item, &revisions);
+        assert_eq!(edits.len(), 2);
+
+        // The newest edit changed only the title; the description's markup
+        // changed but not its text
+        assert_eq!(edits[0].changed_at, at(1_700_003_600));
+        let names: Vec<_> = edits[0].fields.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["Title"]);
+        assert_eq!(edits[0].fields[0].1, diff_words("Launch day", "Launch delayed"));
+
+        assert_eq!(edits[1].changed_at, at(1_700_000_000));
+        let names: Vec<_> = edits[1].fields.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["Description", "Content"]);
+        assert_eq!(edits[1].fields[0].1, diff_words("We launch today.", "We launched today."));
+        assert_eq!(edits[1].fields[1].1, vec![DiffSpan { tag: ChangeTag::Insert, text: "Details".to_string() }]);
+    }
+
+    #[test]
+    fn diffs_words() {
+        let span = |tag, text: &str| DiffSpan { tag, text: text.to_string() };
+        assert_eq!(
+            diff_words("The quick brown fox", "The slow brown fox jumps"),
+            vec![
+                span(ChangeTag::Equal, "The "),
+                span(ChangeTag::Delete, "quick"),
+                span(ChangeTag::Insert, "slow"),
+                span(ChangeTag::Equal, " brown fox"),
+                span(ChangeTag::Insert, " jumps"),
+            ]
+        );
+    }
+}
@@ -1,42 +1,343 @@
-use crate::{db, models::Feed};
+use crate::{
+    config::Config,
+    credentials::BasicCredentials,
+    dates,
+    db,
+    discovery,
+    encoding,
+    http_client::{self, HttpClient},
+    media::{self, MediaRss},
+    models::{Feed, FeedKind, NewEnclosure, NewItem, ScrapeRules},
+    sanitize,
+    scheduler::{self, ChannelSchedule},
+    scrape,
+    websub::{self, WebSubLinks},
+};
 use anyhow::{Context, Result};
+use quick_xml::events::Event;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
 use rss::Channel;
+use serde::Deserialize;
 use sqlx::PgPool;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use time::OffsetDateTime;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 use uuid::Uuid;
 
-pub async fn fetch_and_parse_feed(url: &str) -> Result<Channel> {
-    let content = if url.starts_with("http://") || url.starts_with("https://") {
-        let response = reqwest::get(url).await?;
-        response.bytes().await?
-    } else {
-        // Treat as raw XML content
-        url.as_bytes().to_vec().into()
+/// A feed document normalized from whichever syndication format it was
+/// published in.
+#[derive(Debug, Clone)]
+pub struct ParsedFeed {
+    pub title: String,
+    pub description: Option<String>,
+    pub items: Vec<NewItem>,
+    pub schedule: ChannelSchedule,
+    pub websub: WebSubLinks,
+}
+
+/// HTTP cache validators remembered from a previous response.
+#[derive(Debug, Clone, Default)]
+pub struct CacheValidators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl CacheValidators {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name| headers.get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+        Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+
+    /// `If-None-Match`/`If-Modified-Since` headers for a conditional request.
+    fn request_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(etag) = self.etag.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+            headers.insert(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = self.last_modified.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+            headers.insert(IF_MODIFIED_SINCE, last_modified);
+        }
+        headers
+    }
+}
+
+pub enum FetchOutcome {
+    /// The server answered 304; the stored copy is still current
+    NotModified {
+        moved_to: Option<String>,
+    },
+    Fetched {
+        feed: Box<ParsedFeed>,
+        validators: CacheValidators,
+        /// HTTP status of the response, if the feed came from the network
+        status: Option<StatusCode>,
+        /// Where the feed has permanently moved, if the request was answered
+        /// through 301/308 redirects only
+        moved_to: Option<String>,
+    },
+}
+
+impl FetchOutcome {
+    pub fn moved_to(&self) -> Option<&str> {
+        match self {
+            FetchOutcome::NotModified { moved_to } | FetchOutcome::Fetched { moved_to, .. } => moved_to.as_deref(),
+        }
+    }
+}
+
+/// The URL pointed at a web page instead of a feed. Carries the page so the
+/// caller can look for the feeds it advertises.
+#[derive(Debug, thiserror::Error)]
+#[error("{url} is an HTML page, not a feed")]
+pub struct HtmlPageError {
+    pub url: String,
+    pub html: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedFormat {
+    Rss,
+    Rdf,
+    Atom,
+    JsonFeed,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonFeed {
+    version: String,
+    title: String,
+    description: Option<String>,
+    #[serde(default)]
+    authors: Vec<JsonFeedAuthor>,
+    // JSON Feed 1.0 used a single `author` object
+    author: Option<JsonFeedAuthor>,
+    feed_url: Option<String>,
+    #[serde(default)]
+    hubs: Vec<JsonFeedHub>,
+    #[serde(default)]
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonFeedHub {
+    #[serde(rename = "type")]
+    kind: String,
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonFeedItem {
+    id: Option<serde_json::Value>,
+    url: Option<String>,
+    external_url: Option<String>,
+    title: Option<String>,
+    content_html: Option<String>,
+    content_text: Option<String>,
+    summary: Option<String>,
+    date_published: Option<String>,
+    date_modified: Option<String>,
+    #[serde(default)]
+    authors: Vec<JsonFeedAuthor>,
+    author: Option<JsonFeedAuthor>,
+    image: Option<String>,
+    #[serde(default)]
+    attachments: Vec<JsonFeedAttachment>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonFeedAuthor {
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonFeedAttachment {
+    url: String,
+    mime_type: Option<String>,
+    title: Option<String>,
+    size_in_bytes: Option<f64>,
+    duration_in_seconds: Option<f64>,
+}
+
+pub async fn fetch_and_parse_feed(
+    client: &HttpClient,
+    url: &str,
+    request_headers: &[(String, String)],
+) -> Result<ParsedFeed> {
+    match fetch_feed(client, url, &CacheValidators::default(), request_headers).await? {
+        FetchOutcome::Fetched { feed, .. } => Ok(*feed),
+        FetchOutcome::NotModified { .. } => anyhow::bail!("Unexpected 304 Not Modified for unconditional request"),
+    }
+}
+
+/// Fetch a feed, sending `If-None-Match`/`If-Modified-Since` from the given
+/// validators so an unchanged feed costs a single 304 round trip.
+/// `request_headers` are a private feed's custom headers.
+pub async fn fetch_feed(
+    client: &HttpClient,
+    url: &str,
+    validators: &CacheValidators,
+    request_headers: &[(String, String)],
+) -> Result<FetchOutcome> {
+    if url.starts_with("file://") {
+        return fetch_local_feed(client, url, validators).await;
+    }
+
+    let (response, moved_to) = client.get(url, &validators.request_headers(), &http_client::header_map(request_headers)).await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(FetchOutcome::NotModified { moved_to });
+    }
+
+    let response = response.error_for_status()?;
+    let status = response.status();
+    let final_url = response.url().to_string();
+    let validators = CacheValidators::from_headers(response.headers());
+    let websub_links = WebSubLinks::from_headers(response.headers());
+    let content_type = response.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let body = client.read_body(response).await?;
+    let content = encoding::decode_to_utf8(&body, content_type.as_deref());
+
+    if detect_format(&content).is_none() && discovery::is_html_document(content_type.as_deref(), &content) {
+        return Err(HtmlPageError {
+            url: final_url,
+            html: String::from_utf8_lossy(&content).into_owned(),
+        }
+        .into());
+    }
+
+    let mut feed = parse_feed(&content)?;
+    feed.websub = websub_links.or(feed.websub);
+
+    Ok(FetchOutcome::Fetched {
+        feed: Box::new(feed),
+        validators,
+        status: Some(status),
+        moved_to,
+    })
+}
+
+/// Fetch a web page and build a feed from it with the given scrape rules.
+/// Redirects are followed but never move the feed: the rules were written
+/// for the page at this URL.
+pub async fn fetch_scraped_page(
+    client: &HttpClient,
+    url: &str,
+    validators: &CacheValidators,
+    request_headers: &[(String, String)],
+    rules: &ScrapeRules,
+) -> Result<FetchOutcome> {
+    let (response, _) = client.get(url, &validators.request_headers(), &http_client::header_map(request_headers)).await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(FetchOutcome::NotModified { moved_to: None });
+    }
+
+    let response = response.error_for_status()?;
+    let status = response.status();
+    let final_url = response.url().to_string();
+    let validators = CacheValidators::from_headers(response.headers());
+    let content_type = response.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let body = client.read_body(response).await?;
+    let content = encoding::decode_to_utf8(&body, content_type.as_deref());
+
+    Ok(FetchOutcome::Fetched {
+        feed: Box::new(scrape::scrape_page(&String::from_utf8_lossy(&content), &final_url, rules)?),
+        validators,
+        status: Some(status),
+        moved_to: None,
+    })
+}
+
+/// Read a `file://` feed, using the file's modification time in place of
+/// `Last-Modified` so an untouched file is not parsed again.
+async fn fetch_local_feed(client: &HttpClient, url: &str, validators: &CacheValidators) -> Result<FetchOutcome> {
+    let Some((body, modified)) = client.read_file(url, validators.last_modified.as_deref()).await? else {
+        return Ok(FetchOutcome::NotModified { moved_to: None });
     };
+    let content = encoding::decode_to_utf8(&body, None);
+
+    Ok(FetchOutcome::Fetched {
+        feed: Box::new(parse_feed(&content)?),
+        validators: CacheValidators {
+            etag: None,
+            last_modified: Some(modified),
+        },
+        status: None,
+        moved_to: None,
+    })
+}
+
+/// Sniff the syndication format from the document's root element, or from a
+/// leading `{` for JSON Feed.
+pub fn detect_format(content: &[u8]) -> Option<FeedFormat> {
+    let trimmed = content.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(content);
+    if trimmed.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{') {
+        return Some(FeedFormat::JsonFeed);
+    }
 
-    let channel = Channel::read_from(&content[..])
+    let mut reader = quick_xml::Reader::from_reader(content);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element)) | Ok(Event::Empty(element)) => {
+                return match element.local_name().as_ref() {
+                    b"rss" => Some(FeedFormat::Rss),
+                    b"RDF" => Some(FeedFormat::Rdf),
+                    b"feed" => Some(FeedFormat::Atom),
+                    _ => None,
+                };
+            }
+            Ok(Event::Eof) | Err(_) => return None,
+            _ => {}
+        }
+    }
+}
+
+pub fn parse_feed(content: &[u8]) -> Result<ParsedFeed> {
+    match detect_format(content) {
+        Some(FeedFormat::Atom) => parse_atom(content),
+        Some(FeedFormat::JsonFeed) => parse_json_feed(content),
+        // RSS 1.0 (RDF) is read by the same parser
+        _ => parse_rss(content),
+    }
+}
+
+/// RSS 2.0, and RSS 1.0 documents rooted at `<rdf:RDF>`, which the `rss`
+/// crate reads into the same `Channel`. RSS 1.0 items carry their dates and
+/// authors in the Dublin Core extension instead of `<pubDate>`/`<author>`.
+fn parse_rss(content: &[u8]) -> Result<ParsedFeed> {
+    let channel = Channel::read_from(content)
         .context("Failed to parse RSS feed")?;
-    
-    Ok(channel)
+
+    Ok(channel_to_parsed_feed(channel))
 }
 
-pub async fn update_feed_items(pool: &PgPool, feed_id: Uuid, channel: &Channel) -> Result<()> {
-    // Update feed metadata
-    db::update_feed_metadata(
-        pool,
-        feed_id,
-        &channel.title,
-        Some(&channel.description),
-    )
-    .await?;
+fn channel_to_parsed_feed(channel: Channel) -> ParsedFeed {
+    let channel_image = channel.itunes_ext.as_ref().and_then(|itunes| itunes.image.clone());
+
+    let items = channel.items.iter().map(|rss_item| {
+        let dublin_core = rss_item.dublin_core_ext.as_ref();
+        let itunes = rss_item.itunes_ext.as_ref();
+        let duration_seconds = itunes
+            .and_then(|itunes| itunes.duration.as_deref())
+            .and_then(dates::parse_duration);
 
-    // Process items
-    for rss_item in &channel.items {
         let guid = rss_item.guid
             .as_ref()
             .map(|g| g.value.clone())
             .or_else(|| rss_item.link.clone())
             .unwrap_or_else(|| {
-                format!("{}-{}", 
+                format!("{}-{}",
                     rss_item.title.as_deref().unwrap_or("no-title"),
                     rss_item.pub_date.as_deref().unwrap_or("no-date")
                 )
@@ -44,65 +345,570 @@ pub async fn update_feed_items(pool: &PgPool, feed_id: Uuid, channel: &Channel)
 
         let pub_date = rss_item.pub_date
             .as_ref()
-            .and_then(|date_str| {
-                // Try to parse various date formats
-                time::OffsetDateTime::parse(date_str, &time::format_description::well_known::Rfc2822).ok()
-                    .or_else(|| time::OffsetDateTime::parse(date_str, &time::format_description::well_known::Rfc3339).ok())
+            .and_then(|date_str| dates::parse_date(date_str))
+            .or_else(|| {
+                dublin_core
+                    .and_then(|dc| dc.dates.first())
+                    .and_then(|date_str| dates::parse_date(date_str))
             });
 
-        let title = rss_item.title.clone().unwrap_or_else(|| "Untitled".to_string());
-        let description = rss_item.description.as_deref();
-        let link = rss_item.link.as_deref();
-        let author = rss_item.author.as_deref()
-            .or_else(|| rss_item.dublin_core_ext.as_ref().and_then(|dc| dc.creators.first().map(|s| s.as_str())));
-        let content = rss_item.content.as_deref();
-
-        db::create_or_update_item(
-            pool, 
-            feed_id, 
-            &guid, 
-            &title, 
-            description, 
-            link, 
-            pub_date, 
-            author, 
-            content
-        ).await?;
+        let author = rss_item.author.clone()
+            .or_else(|| dublin_core.and_then(|dc| dc.creators.first().cloned()));
+
+        let mut item = NewItem {
+            guid,
+            title: rss_item.title.clone().unwrap_or_else(|| "Untitled".to_string()),
+            description: rss_item.description.clone(),
+            link: rss_item.link.clone(),
+            pub_date,
+            author,
+            content: rss_item.content.clone(),
+            enclosures: rss_item.enclosure.iter().map(|enclosure| NewEnclosure {
+                url: enclosure.url.clone(),
+                mime_type: Some(enclosure.mime_type.clone()).filter(|t| !t.is_empty()),
+                length: enclosure.length.trim().parse().ok().filter(|length| *length > 0),
+                title: None,
+                duration_seconds,
+            }).collect(),
+            duration_seconds,
+            episode: itunes.and_then(|itunes| itunes.episode.as_deref()).and_then(|e| e.trim().parse().ok()),
+            season: itunes.and_then(|itunes| itunes.season.as_deref()).and_then(|s| s.trim().parse().ok()),
+            image_url: itunes.and_then(|itunes| itunes.image.clone()).or_else(|| channel_image.clone()),
+            ..Default::default()
+        };
+        sanitize_item(&mut item);
+        apply_media(&mut item, media::from_rss_extensions(&rss_item.extensions, &channel.namespaces));
+        item
+    }).collect();
+
+    ParsedFeed {
+        schedule: ChannelSchedule::from_channel(&channel),
+        websub: channel_websub_links(&channel),
+        title: channel.title,
+        description: Some(channel.description),
+        items,
+    }
+}
+
+/// Hub and self links from `<atom:link>` elements in an RSS channel.
+fn channel_websub_links(channel: &Channel) -> WebSubLinks {
+    const ATOM_NAMESPACE: &str = "http://www.w3.org/2005/Atom";
+
+    let links: Vec<_> = channel.namespaces
+        .iter()
+        .filter(|(_, uri)| *uri == ATOM_NAMESPACE)
+        .filter_map(|(prefix, _)| channel.extensions.get(prefix)?.get("link"))
+        .flatten()
+        .collect();
+    let href = |rel: &str| {
+        links.iter()
+            .find(|link| link.attrs.get("rel").is_some_and(|r| r == rel))
+            .and_then(|link| link.attrs.get("href").cloned())
+    };
+
+    WebSubLinks {
+        hub: href("hub"),
+        topic: href("self"),
     }
+}
+
+fn parse_atom(content: &[u8]) -> Result<ParsedFeed> {
+    let feed = atom_syndication::Feed::read_from(content)
+        .context("Failed to parse Atom feed")?;
+
+    let feed_author = feed.authors.first().map(|a| a.name.clone());
+
+    let items = feed.entries.iter().map(|entry| {
+        // Prefer the rel="alternate" link, which points at the HTML version of the entry
+        let link = entry.links
+            .iter()
+            .find(|l| l.rel == "alternate")
+            .or_else(|| entry.links.first())
+            .map(|l| l.href.clone());
+
+        let guid = Some(entry.id.clone())
+            .filter(|id| !id.is_empty())
+            .or_else(|| link.clone())
+            .unwrap_or_else(|| format!("{}-{}", entry.title.value, entry.updated.to_rfc3339()));
+
+        let pub_date = entry.published
+            .as_ref()
+            .or(Some(&entry.updated))
+            .and_then(atom_date_to_offset_date_time);
+
+        let author = entry.authors
+            .first()
+            .map(|a| a.name.clone())
+            .or_else(|| feed_author.clone());
+
+        let title = Some(entry.title.value.clone())
+            .filter(|t| !t.is_empty())
+            .unwrap_or_else(|| "Untitled".to_string());
+
+        let mut item = NewItem {
+            guid,
+            title,
+            description: entry.summary.as_ref().map(|s| s.value.clone()),
+            link,
+            pub_date,
+            author,
+            content: entry.content.as_ref().and_then(|c| c.value.clone()),
+            enclosures: entry.links
+                .iter()
+                .filter(|l| l.rel == "enclosure")
+                .map(|l| NewEnclosure {
+                    url: l.href.clone(),
+                    mime_type: l.mime_type.clone(),
+                    length: l.length.as_deref().and_then(|length| length.trim().parse().ok()),
+                    title: l.title.clone(),
+                    duration_seconds: None,
+                })
+                .collect(),
+            ..Default::default()
+        };
+        sanitize_item(&mut item);
+        apply_media(&mut item, media::from_atom_extensions(&entry.extensions, &feed.namespaces));
+        item
+    }).collect();
+
+    let href = |rel: &str| feed.links.iter().find(|l| l.rel == rel).map(|l| l.href.clone());
+    let websub = WebSubLinks {
+        hub: href("hub"),
+        topic: href("self"),
+    };
+
+    Ok(ParsedFeed {
+        title: feed.title.value,
+        description: feed.subtitle.map(|s| s.value),
+        items,
+        schedule: ChannelSchedule::default(),
+        websub,
+    })
+}
+
+fn parse_json_feed(content: &[u8]) -> Result<ParsedFeed> {
+    let content = content.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(content);
+    let feed: JsonFeed = serde_json::from_slice(content)
+        .context("Failed to parse JSON feed")?;
+
+    if !feed.version.starts_with("https://jsonfeed.org/version/") {
+        anyhow::bail!("Unsupported JSON feed version: {}", feed.version);
+    }
+
+    let feed_author = feed.authors
+        .iter()
+        .chain(feed.author.as_ref())
+        .find_map(|a| a.name.clone());
+
+    let items = feed.items.into_iter().map(|item| {
+        let link = item.url.clone().or_else(|| item.external_url.clone());
+
+        let guid = match &item.id {
+            Some(serde_json::Value::String(id)) if !id.is_empty() => Some(id.clone()),
+            Some(serde_json::Value::Number(id)) => Some(id.to_string()),
+            _ => None,
+        }
+        .or_else(|| link.clone())
+        .unwrap_or_else(|| {
+            format!("{}-{}",
+                item.title.as_deref().unwrap_or("no-title"),
+                item.date_published.as_deref().unwrap_or("no-date")
+            )
+        });
+
+        let pub_date = item.date_published
+            .as_deref()
+            .or(item.date_modified.as_deref())
+            .and_then(dates::parse_date);
+
+        let author = item.authors
+            .iter()
+            .chain(item.author.as_ref())
+            .find_map(|a| a.name.clone())
+            .or_else(|| feed_author.clone());
+
+        let enclosures: Vec<NewEnclosure> = item.attachments.into_iter().map(|attachment| NewEnclosure {
+            url: attachment.url,
+            mime_type: attachment.mime_type,
+            length: attachment.size_in_bytes.map(|size| size as i64),
+            title: attachment.title,
+            duration_seconds: attachment.duration_in_seconds.map(|secs| secs as i64),
+        }).collect();
+
+        let mut item = NewItem {
+            guid,
+            title: item.title.filter(|t| !t.is_empty()).unwrap_or_else(|| "Untitled".to_string()),
+            description: item.summary,
+            link,
+            pub_date,
+            author,
+            content: item.content_html.or_else(|| item.content_text.as_deref().map(text_to_html)),
+            duration_seconds: enclosures.iter().find_map(|enclosure| enclosure.duration_seconds),
+            enclosures,
+            image_url: item.image,
+            ..Default::default()
+        };
+        sanitize_item(&mut item);
+        item.thumbnail_url = first_html_image(&item);
+        item
+    }).collect();
+
+    Ok(ParsedFeed {
+        title: feed.title,
+        description: feed.description,
+        items,
+        schedule: ChannelSchedule::default(),
+        websub: WebSubLinks {
+            hub: feed.hubs.into_iter().find(|hub| hub.kind.eq_ignore_ascii_case("websub")).map(|hub| hub.url),
+            topic: feed.feed_url,
+        },
+    })
+}
+
+/// Plain text as HTML: escaped, with blank lines separating paragraphs and
+/// single newlines kept as line breaks.
+fn text_to_html(text: &str) -> String {
+    let escape = |text: &str| text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+    text.replace("\r\n", "\n")
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| format!("<p>{}</p>", paragraph.lines().map(escape).collect::<Vec<_>>().join("<br>")))
+        .collect()
+}
+
+/// Run an item's HTML through the sanitizer before it is stored, and drop
+/// a link that is not a web page, such as `javascript:`, since it ends up
+/// in an `href`.
+fn sanitize_item(item: &mut NewItem) {
+    item.link = item.link.take().filter(|link| {
+        url::Url::parse(link.trim()).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+    });
+    let base = item.link.as_deref();
+    item.description = item.description.as_deref().map(|html| sanitize::clean_html(html, base));
+    item.content = item.content.as_deref().map(|html| sanitize::clean_html(html, base));
+}
+
+/// Fold an item's Media RSS into it: extra media files join its enclosures,
+/// and without a Media RSS thumbnail the first image in its HTML is used.
+fn apply_media(item: &mut NewItem, media: MediaRss) {
+    for content in media.content {
+        if !item.enclosures.iter().any(|enclosure| enclosure.url == content.url) {
+            item.enclosures.push(content);
+        }
+    }
+    item.thumbnail_url = media.thumbnail.or_else(|| first_html_image(item));
+}
+
+/// The first image in an item's description or content.
+fn first_html_image(item: &NewItem) -> Option<String> {
+    [&item.description, &item.content]
+        .into_iter()
+        .flatten()
+        .find_map(|html| media::first_image(html, item.link.as_deref()))
+}
+
+fn atom_date_to_offset_date_time(date: &atom_syndication::FixedDateTime) -> Option<OffsetDateTime> {
+    let offset = time::UtcOffset::from_whole_seconds(date.offset().local_minus_utc()).ok()?;
+    let timestamp = i128::from(date.timestamp()) * 1_000_000_000 + i128::from(date.timestamp_subsec_nanos());
+    OffsetDateTime::from_unix_timestamp_nanos(timestamp)
+        .ok()
+        .map(|dt| dt.to_offset(offset))
+}
+
+pub async fn update_feed_items(pool: &PgPool, feed_id: Uuid, feed: &ParsedFeed) -> Result<()> {
+    // Update feed metadata
+    db::update_feed_metadata(
+        pool,
+        feed_id,
+        &feed.title,
+        feed.description.as_deref(),
+    )
+    .await?;
+
+    // Process items
+    let mut new_items = 0;
+    for item in &feed.items {
+        let (_, inserted) = db::create_or_update_item(pool, feed_id, item).await?;
+        if inserted {
+            new_items += 1;
+        }
+    }
+
+    scheduler::schedule_next_fetch(pool, feed_id, &feed.schedule, new_items).await?;
 
     Ok(())
 }
 
-pub async fn fetch_all_user_feeds(pool: &PgPool, user_id: Uuid) -> Result<()> {
-    let subscriptions = db::get_user_subscriptions(pool, user_id).await?;
-    
-    // Get feed URLs for all subscriptions
-    let feed_ids: Vec<Uuid> = subscriptions.iter().map(|s| s.feed_id).collect();
-    
-    // Fetch all feeds
-    for feed_id in feed_ids {
-        // Get feed info
-        let feed_result: Result<Feed> = sqlx::query_as::<_, Feed>(
-            "SELECT id, url, title, description, last_fetched, created_at, updated_at FROM feeds WHERE id = $1"
-        )
-        .bind(feed_id)
-        .fetch_one(pool)
-        .await
-        .map_err(Into::into);
-        
-        if let Ok(feed) = feed_result {
-            match fetch_and_parse_feed(&feed.url).await {
-                Ok(channel) => {
-                    if let Err(e) = update_feed_items(pool, feed.id, &channel).await {
-                        log::error!("Failed to update feed {}: {}", feed.url, e);
-                    }
+/// Custom headers for fetching a private feed, with its Basic credentials
+/// (if any) decrypted into an `Authorization` header.
+async fn feed_request_headers(pool: &PgPool, config: &Config, feed_id: Uuid) -> Result<Vec<(String, String)>> {
+    let mut headers = db::get_feed_request_headers(pool, feed_id).await?;
+    if let Some(sealed) = db::get_feed_credentials(pool, feed_id).await? {
+        let credentials = BasicCredentials::open(&sealed, &config.credentials_key)?;
+        headers.retain(|(name, _)| !name.eq_ignore_ascii_case("authorization"));
+        headers.push(("authorization".to_string(), credentials.authorization()));
+    }
+    Ok(headers)
+}
+
+pub async fn refresh_feed(pool: &PgPool, client: &HttpClient, config: &Config, feed: &Feed) -> Result<()> {
+    let validators = CacheValidators {
+        etag: feed.etag.clone(),
+        last_modified: feed.last_modified.clone(),
+    };
+
+    let fetched = async {
+        let request_headers = feed_request_headers(pool, config, feed.id).await?;
+        match feed.kind {
+            FeedKind::Remote => fetch_feed(client, &feed.url, &validators, &request_headers).await,
+            FeedKind::Scraped => {
+                let rules = db::get_scrape_rules(pool, feed.id).await?.context("Scraped feed has no scrape rules")?;
+                fetch_scraped_page(client, &feed.url, &validators, &request_headers, &rules).await
+            }
+            FeedKind::Static => anyhow::bail!("Static feeds are never fetched"),
+        }
+    }
+    .await;
+    // A permanent redirect can merge this feed into one already stored at
+    // the new URL, after which only that feed's id is still valid
+    let mut feed_id = feed.id;
+    let result = match fetched {
+        Ok(outcome) => async {
+            if let Some(new_url) = outcome.moved_to().filter(|url| *url != feed.url) {
+                log::info!("Feed {} moved permanently to {}", feed.url, new_url);
+                feed_id = db::move_feed_url(pool, feed.id, new_url).await?;
+            }
+
+            match outcome {
+                FetchOutcome::NotModified { .. } => {
+                    log::debug!("Feed {} not modified", feed.url);
+                    scheduler::schedule_next_fetch(pool, feed_id, &ChannelSchedule::default(), 0).await?;
+                    db::record_fetch_success(pool, feed_id, Some(StatusCode::NOT_MODIFIED.as_u16())).await
                 }
-                Err(e) => {
-                    log::error!("Failed to fetch feed {}: {}", feed.url, e);
+                FetchOutcome::Fetched { feed: parsed, validators, status, moved_to } => {
+                    // Only remember the validators once the items are stored, so a
+                    // failed update is retried in full rather than answered with 304
+                    update_feed_items(pool, feed_id, &parsed).await?;
+                    db::update_feed_cache_validators(
+                        pool,
+                        feed_id,
+                        validators.etag.as_deref(),
+                        validators.last_modified.as_deref(),
+                    ).await?;
+                    db::record_fetch_success(pool, feed_id, status.map(|s| s.as_u16())).await?;
+
+                    let url = moved_to.as_deref().unwrap_or(&feed.url);
+                    if let Err(e) = websub::ensure_subscription(pool, client, config, feed_id, url, &parsed.websub).await {
+                        log::warn!("Failed to subscribe to WebSub hub for {}: {:#}", url, e);
+                    }
+                    Ok(())
                 }
             }
         }
+        .await
+        .with_context(|| format!("Failed to update feed {}", feed.url)),
+        Err(e) => Err(e.context(format!("Failed to fetch feed {}", feed.url))),
+    };
+
+    if let Err(e) = &result {
+        let status = e.chain()
+            .find_map(|cause| cause.downcast_ref::<reqwest::Error>())
+            .and_then(|e| e.status())
+            .map(|s| s.as_u16());
+        let failures = feed.consecutive_failures + 1;
+        let next = OffsetDateTime::now_utc()
+            + time::Duration::seconds(scheduler::backoff_interval(feed.fetch_interval_secs, failures).into());
+        // 410 Gone means the publisher has retired the feed for good
+        let pause = status == Some(StatusCode::GONE.as_u16()) || failures >= config.max_fetch_failures;
+        if pause {
+            log::warn!("Pausing feed {} after {} consecutive failures: {}", feed.url, failures, e.root_cause());
+        }
+
+        // Store the root cause; the context chain only repeats the feed URL
+        if let Err(db_err) = db::record_fetch_failure(pool, feed_id, &e.root_cause().to_string(), status, next, pause).await {
+            log::error!("Failed to record fetch failure for {}: {:#}", feed.url, db_err);
+        }
+    }
+
+    result
+}
+
+/// Refresh a batch of feeds concurrently, within the fetch limits of
+/// `run_limited`. Failures are logged per feed.
+pub async fn refresh_feeds(pool: &PgPool, client: &HttpClient, config: &Arc<Config>, feeds: Vec<Feed>) {
+    let jobs = feeds.into_iter().map(|feed| (feed.url.clone(), feed)).collect();
+    run_limited(config, jobs, |feed| {
+        let pool = pool.clone();
+        let client = client.clone();
+        let config = config.clone();
+        async move {
+            if let Err(e) = refresh_feed(&pool, &client, &config, &feed).await {
+                log::error!("{:#}", e);
+            }
+        }
+    })
+    .await;
+}
+
+/// Run `task` for every job, each paired with the URL it fetches, running
+/// at most `fetch_concurrency` at once overall and
+/// `fetch_per_host_concurrency` against any single host. Dropping the
+/// returned future aborts the tasks still running.
+pub async fn run_limited<T, F, Fut>(config: &Config, jobs: Vec<(String, T)>, task: F)
+where
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let global = Arc::new(Semaphore::new(config.fetch_concurrency.max(1)));
+    let mut per_host: HashMap<String, Arc<Semaphore>> = HashMap::new();
+    let mut tasks = JoinSet::new();
+
+    for (url, job) in jobs {
+        let host = url::Url::parse(&url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+        let host_limit = per_host
+            .entry(host)
+            .or_insert_with(|| Arc::new(Semaphore::new(config.fetch_per_host_concurrency.max(1))))
+            .clone();
+        let global = global.clone();
+        let work = task(job);
+
+        tasks.spawn(async move {
+            // Wait for the host first so a busy host doesn't tie up global slots
+            let _host_permit = host_limit.acquire_owned().await;
+            let _global_permit = global.acquire_owned().await;
+            work.await;
+        });
     }
-    
+
+    while let Some(result) = tasks.join_next().await {
+        if let Err(e) = result {
+            log::error!("Fetch task failed: {}", e);
+        }
+    }
+}
+
+/// Refresh every feed whose next scheduled fetch has passed, regardless of
+/// who subscribes to it.
+pub async fn refresh_due_feeds(pool: &PgPool, client: &HttpClient, config: &Arc<Config>) -> Result<()> {
+    let feeds = db::get_due_feeds(pool).await?;
+    refresh_feeds(pool, client, config, feeds).await;
     Ok(())
-}
\ No newline at end of file
+}
+
+pub async fn fetch_all_user_feeds(pool: &PgPool, client: &HttpClient, config: &Arc<Config>, user_id: Uuid) -> Result<()> {
+    let feeds = db::get_user_feeds(pool, user_id).await?;
+    refresh_feeds(pool, client, config, feeds).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_atom_entries() {
+        let atom = br#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Example Blog</title>
+  <subtitle>Notes</subtitle>
+  <id>urn:example:blog</id>
+  <updated>2024-02-01T00:00:00Z</updated>
+  <author><name>Feed Author</name></author>
+  <entry>
+    <title>Published entry</title>
+    <id>urn:example:1</id>
+    <link rel="related" href="https://example.com/elsewhere"/>
+    <link rel="alternate" href="https://example.com/posts/1"/>
+    <published>2024-01-10T08:00:00Z</published>
+    <updated>2024-01-20T08:00:00Z</updated>
+    <author><name>Entry Author</name></author>
+    <summary>Short summary</summary>
+    <content type="html">&lt;p&gt;Full text&lt;/p&gt;</content>
+  </entry>
+  <entry>
+    <title></title>
+    <id></id>
+    <link href="https://example.com/posts/2"/>
+    <updated>2024-01-25T08:00:00Z</updated>
+    <summary>Only a summary</summary>
+  </entry>
+</feed>"#;
+        let feed = parse_feed(atom).unwrap();
+        assert_eq!(feed.title, "Example Blog");
+        assert_eq!(feed.description.as_deref(), Some("Notes"));
+        assert_eq!(feed.items.len(), 2);
+
+        let first = &feed.items[0];
+        assert_eq!(first.guid, "urn:example:1");
+        assert_eq!(first.title, "Published entry");
+        assert_eq!(first.link.as_deref(), Some("https://example.com/posts/1"));
+        assert_eq!(first.pub_date, Some(time::macros::datetime!(2024-01-10 08:00 UTC)));
+        assert_eq!(first.author.as_deref(), Some("Entry Author"));
+        assert_eq!(first.description.as_deref(), Some("Short summary"));
+        assert_eq!(first.content.as_deref(), Some("<p>Full text</p>"));
+
+        // Without an id the link stands in; without `published`, `updated` does
+        let second = &feed.items[1];
+        assert_eq!(second.guid, "https://example.com/posts/2");
+        assert_eq!(second.title, "Untitled");
+        assert_eq!(second.pub_date, Some(time::macros::datetime!(2024-01-25 08:00 UTC)));
+        assert_eq!(second.author.as_deref(), Some("Feed Author"));
+        assert_eq!(second.content, None);
+    }
+
+    #[test]
+    fn parses_rss_1_0_with_dublin_core() {
+        let rdf = br#"<?xml version="1.0"?>
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#" xmlns="http://purl.org/rss/1.0/"
+         xmlns:dc="http://purl.org/dc/elements/1.1/">
+  <channel rdf:about="https://example.com/">
+    <title>RDF Site</title>
+    <link>https://example.com/</link>
+    <description>An RSS 1.0 feed</description>
+  </channel>
+  <item rdf:about="https://example.com/1">
+    <title>First</title>
+    <link>https://example.com/1</link>
+    <description>Hello</description>
+    <dc:date>2024-03-05T10:30:00+01:00</dc:date>
+    <dc:creator>Jane Doe</dc:creator>
+  </item>
+</rdf:RDF>"#;
+        assert_eq!(detect_format(rdf), Some(FeedFormat::Rdf));
+        let feed = parse_feed(rdf).unwrap();
+        assert_eq!(feed.title, "RDF Site");
+        assert_eq!(feed.items.len(), 1);
+
+        let item = &feed.items[0];
+        assert_eq!(item.title, "First");
+        assert_eq!(item.link.as_deref(), Some("https://example.com/1"));
+        assert_eq!(item.pub_date, Some(time::macros::datetime!(2024-03-05 09:30 UTC)));
+        assert_eq!(item.author.as_deref(), Some("Jane Doe"));
+    }
+
+    #[test]
+    fn json_feed_text_content_becomes_html() {
+        let json = br#"{"version": "https://jsonfeed.org/version/1.1", "title": "T", "items": [
+            {"id": "1", "content_text": "if a < b && c > d\nthen swap\n\nSecond paragraph"}]}"#;
+        let feed = parse_feed(json).unwrap();
+        assert_eq!(
+            feed.items[0].content.as_deref(),
+            Some("<p>if a &lt; b &amp;&amp; c &gt; d<br>then swap</p><p>Second paragraph</p>")
+        );
+    }
+
+    #[test]
+    fn drops_links_that_are_not_web_pages() {
+        let rss = br#"<rss version="2.0"><channel><title>T</title><link>https://example.com/</link><description>D</description>
+            <item><title>Script</title><guid>1</guid><link>javascript:alert(document.cookie)</link></item>
+            <item><title>Data</title><guid>2</guid><link> DATA:text/html,hi</link></item>
+            <item><title>Web</title><guid>3</guid><link>https://example.com/posts/3</link></item>
+        </channel></rss>"#;
+        let links: Vec<_> = parse_feed(rss).unwrap().items.into_iter().map(|item| item.link).collect();
+        assert_eq!(links, [None, None, Some("https://example.com/posts/3".to_string())]);
+    }
+}
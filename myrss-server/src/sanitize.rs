@@ -0,0 +1,69 @@
+use crate::db;
+use ammonia::{Builder, Url, UrlRelative};
+use anyhow::Result;
+use sqlx::PgPool;
+
+/// Items cleaned per round trip by the startup backfill.
+const BACKFILL_BATCH: i64 = 500;
+
+/// Clean feed-supplied HTML down to ammonia's allowlist of tags and
+/// attributes. Scripts, styles, frames and event handlers are dropped,
+/// relative URLs are resolved against `base` (the item's link) or removed
+/// when there is none, and every link gets `rel="noopener noreferrer"`.
+pub fn clean_html(html: &str, base: Option<&str>) -> String {
+    let relative = match base.and_then(|base| Url::parse(base).ok()) {
+        Some(base) => UrlRelative::RewriteWithBase(base),
+        None => UrlRelative::Deny,
+    };
+
+    Builder::default()
+        .link_rel(Some("noopener noreferrer"))
+        .url_relative(relative)
+        .clean(html)
+        .to_string()
+}
+
+/// Clean the HTML of items stored before ingest sanitized it, so pages can
+/// show stored HTML as is. Runs at startup, before anything is served.
+pub async fn backfill(pool: &PgPool) -> Result<()> {
+    let mut cleaned = 0;
+    loop {
+        let items = db::get_unsanitized_items(pool, BACKFILL_BATCH).await?;
+        if items.is_empty() {
+            break;
+        }
+        for item in &items {
+            let base = item.link.as_deref();
+            let description = item.description.as_deref().map(|html| clean_html(html, base));
+            let content = item.content.as_deref().map(|html| clean_html(html, base));
+            db::set_item_sanitized_html(pool, item.id, description.as_deref(), content.as_deref()).await?;
+        }
+        cleaned += items.len();
+    }
+
+    if cleaned > 0 {
+        log::info!("Sanitized the HTML of {} items stored by an earlier version", cleaned);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::clean_html;
+
+    #[test]
+    fn strips_active_content() {
+        let html = r#"<p onclick="steal()">Hi<script>alert(1)</script><style>p{}</style></p><iframe src="https://evil.example/"></iframe><a href="javascript:alert(1)">x</a>"#;
+        assert_eq!(clean_html(html, None), r#"<p>Hi</p><a rel="noopener noreferrer">x</a>"#);
+    }
+
+    #[test]
+    fn resolves_relative_urls() {
+        let html = r#"<a href="/post/2" target="_blank">next</a><img src="img/a.png">"#;
+        assert_eq!(
+            clean_html(html, Some("https://example.com/blog/post/1")),
+            r#"<a href="https://example.com/post/2" rel="noopener noreferrer">next</a><img src="https://example.com/blog/post/img/a.png">"#
+        );
+        assert_eq!(clean_html(html, None), r#"<a rel="noopener noreferrer">next</a><img>"#);
+    }
+}
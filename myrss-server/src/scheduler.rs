@@ -0,0 +1,160 @@
+use crate::{article, db, rss_fetcher, websub, AppState};
+use anyhow::Result;
+use rss::Channel;
+use sqlx::PgPool;
+use std::time::Duration;
+use time::{OffsetDateTime, Weekday};
+use tokio::time::MissedTickBehavior;
+use uuid::Uuid;
+
+/// Lower bound on the adaptive polling interval (5 minutes).
+pub const MIN_FETCH_INTERVAL_SECS: i32 = 300;
+/// Upper bound on the adaptive polling interval (1 day).
+pub const MAX_FETCH_INTERVAL_SECS: i32 = 86_400;
+
+/// Publisher-supplied caching hints from an RSS `<channel>`.
+#[derive(Debug, Clone, Default)]
+pub struct ChannelSchedule {
+    /// `<ttl>`: minutes the channel may be cached before refreshing
+    pub ttl_minutes: Option<u32>,
+    /// `<skipHours>`: hours of the day (GMT) during which not to fetch
+    pub skip_hours: Vec<u8>,
+    /// `<skipDays>`: days of the week (GMT) during which not to fetch
+    pub skip_days: Vec<Weekday>,
+}
+
+impl ChannelSchedule {
+    pub fn from_channel(channel: &Channel) -> Self {
+        Self {
+            ttl_minutes: channel.ttl.as_deref().and_then(|ttl| ttl.trim().parse().ok()),
+            skip_hours: channel.skip_hours
+                .iter()
+                .filter_map(|hour| hour.trim().parse().ok())
+                .filter(|hour| *hour < 24)
+                .collect(),
+            skip_days: channel.skip_days
+                .iter()
+                .filter_map(|day| day.trim().parse().ok())
+                .collect(),
+        }
+    }
+
+    fn skips(&self, at: OffsetDateTime) -> bool {
+        self.skip_hours.contains(&at.hour()) || self.skip_days.contains(&at.weekday())
+    }
+}
+
+/// Halve the interval when a fetch turned up new items and back off by half
+/// again when it did not, so busy feeds converge on frequent polling and
+/// quiet ones drift towards daily.
+pub fn adapt_interval(current_secs: i32, new_items: usize) -> i32 {
+    let next = if new_items > 0 {
+        current_secs / 2
+    } else {
+        current_secs.saturating_add(current_secs / 2)
+    };
+    next.clamp(MIN_FETCH_INTERVAL_SECS, MAX_FETCH_INTERVAL_SECS)
+}
+
+/// The interval actually used for the next fetch. A subscriber's override
+/// wins outright; otherwise the adaptive interval is never shorter than the
+/// publisher's `<ttl>`.
+pub fn effective_interval(adaptive_secs: i32, schedule: &ChannelSchedule, override_secs: Option<i32>) -> i32 {
+    if let Some(secs) = override_secs {
+        return secs.max(MIN_FETCH_INTERVAL_SECS);
+    }
+    let ttl_secs = schedule.ttl_minutes
+        .map(|minutes| i32::try_from(minutes.saturating_mul(60)).unwrap_or(i32::MAX))
+        .unwrap_or(0);
+    adaptive_secs.max(ttl_secs.min(MAX_FETCH_INTERVAL_SECS))
+}
+
+/// Delay before retrying a feed that has failed `failures` times in a row:
+/// its normal interval doubled per failure, capped at one day.
+pub fn backoff_interval(interval_secs: i32, failures: i32) -> i32 {
+    let exponent = failures.saturating_sub(1).clamp(0, 30) as u32;
+    interval_secs
+        .saturating_mul(2_i32.saturating_pow(exponent))
+        .clamp(MIN_FETCH_INTERVAL_SECS, MAX_FETCH_INTERVAL_SECS)
+}
+
+/// Earliest time at least `interval_secs` after `now` that does not fall in
+/// the channel's skip hours or skip days.
+pub fn next_fetch_at(now: OffsetDateTime, interval_secs: i32, schedule: &ChannelSchedule) -> OffsetDateTime {
+    let mut next = now.to_offset(time::UtcOffset::UTC) + time::Duration::seconds(interval_secs.into());
+
+    // A week of hours covers every combination of skip rules; if every hour
+    // is skipped the channel is misconfigured and we fetch anyway
+    for _ in 0..(24 * 7) {
+        if !schedule.skips(next) {
+            return next;
+        }
+        next = next.replace_minute(0)
+            .and_then(|t| t.replace_second(0))
+            .and_then(|t| t.replace_nanosecond(0))
+            .unwrap_or(next)
+            + time::Duration::HOUR;
+    }
+    next
+}
+
+/// Record how long to wait before fetching this feed again, given how many
+/// new items the fetch that just finished found.
+pub async fn schedule_next_fetch(
+    pool: &PgPool,
+    feed_id: Uuid,
+    schedule: &ChannelSchedule,
+    new_items: usize,
+) -> Result<()> {
+    let current = db::get_feed(pool, feed_id).await?.fetch_interval_secs;
+    let override_secs = db::get_feed_interval_override(pool, feed_id).await?;
+
+    let adaptive = adapt_interval(current, new_items);
+    // A feed its hub pushes to only needs the occasional poll in case the
+    // hub goes quiet
+    let interval = if override_secs.is_none() && db::has_active_websub_subscription(pool, feed_id).await? {
+        MAX_FETCH_INTERVAL_SECS
+    } else {
+        effective_interval(adaptive, schedule, override_secs)
+    };
+    let next = next_fetch_at(OffsetDateTime::now_utc(), interval, schedule);
+
+    db::update_feed_schedule(pool, feed_id, adaptive, next).await
+}
+
+/// Spawn the background worker that periodically refreshes feeds that are due,
+/// renews WebSub leases and downloads full articles.
+///
+/// Runs on its own task so HTTP handlers never wait on feed fetches. An
+/// interval of zero disables the worker entirely.
+pub fn spawn(state: AppState) {
+    if state.config.refresh_interval_secs == 0 {
+        log::info!("Background feed refresh disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(state.config.refresh_interval_secs));
+        // A slow refresh pass should push the next one back rather than
+        // trigger a burst of catch-up runs
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            log::debug!("Starting background feed refresh");
+            match rss_fetcher::refresh_due_feeds(&state.db_pool, &state.http_client, &state.config).await {
+                Ok(()) => log::debug!("Background feed refresh complete"),
+                Err(e) => log::error!("Background feed refresh failed: {:#}", e),
+            }
+
+            if let Err(e) = websub::renew_due_subscriptions(&state.db_pool, &state.http_client, &state.config).await {
+                log::error!("WebSub renewal failed: {:#}", e);
+            }
+
+            if let Err(e) = article::fill_full_content(&state.db_pool, &state.http_client, &state.config).await {
+                log::error!("Full content download failed: {:#}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,183 @@
+use crate::{dates, media, models::{NewItem, ScrapeRules}, rss_fetcher::ParsedFeed, sanitize};
+use anyhow::{anyhow, Result};
+use scraper::{ElementRef, Html, Selector};
+use std::collections::HashSet;
+use url::Url;
+
+/// Tidy selectors entered in a form, dropping empty optional ones, and make
+/// sure every one of them parses.
+pub fn check_rules(rules: ScrapeRules) -> Result<ScrapeRules> {
+    let optional = |selector: Option<String>| selector.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let rules = ScrapeRules {
+        item_selector: rules.item_selector.trim().to_string(),
+        title_selector: rules.title_selector.trim().to_string(),
+        link_selector: optional(rules.link_selector),
+        date_selector: optional(rules.date_selector),
+        body_selector: optional(rules.body_selector),
+    };
+    Selectors::parse(&rules)?;
+    Ok(rules)
+}
+
+struct Selectors {
+    item: Selector,
+    title: Selector,
+    link: Option<Selector>,
+    date: Option<Selector>,
+    body: Option<Selector>,
+}
+
+impl Selectors {
+    fn parse(rules: &ScrapeRules) -> Result<Self> {
+        let parse = |name: &str, selector: &str| {
+            if selector.is_empty() {
+                return Err(anyhow!("The {} selector is required", name));
+            }
+            Selector::parse(selector).map_err(|e| anyhow!("Invalid {} selector `{}`: {}", name, selector, e))
+        };
+        let optional = |name: &str, selector: &Option<String>| selector.as_deref().map(|s| parse(name, s)).transpose();
+
+        Ok(Self {
+            item: parse("item", &rules.item_selector)?,
+            title: parse("title", &rules.title_selector)?,
+            link: optional("link", &rules.link_selector)?,
+            date: optional("date", &rules.date_selector)?,
+            body: optional("body", &rules.body_selector)?,
+        })
+    }
+}
+
+/// Build a feed from a web page: every match of the item selector with a
+/// non-empty title becomes an item. Links are resolved against the page;
+/// without a link selector, the item's own `href` or first link is used.
+pub fn scrape_page(html: &str, page_url: &str, rules: &ScrapeRules) -> Result<ParsedFeed> {
+    let selectors = Selectors::parse(rules)?;
+    let base = Url::parse(page_url)?;
+    let document = Html::parse_document(html);
+
+    let title = Selector::parse("title")
+        .ok()
+        .and_then(|selector| document.select(&selector).next())
+        .map(text)
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| page_url.to_string());
+
+    let mut seen = HashSet::new();
+    let mut items = Vec::new();
+    for container in document.select(&selectors.item) {
+        let Some(item_title) = container.select(&selectors.title).next().map(text).filter(|t| !t.is_empty()) else {
+            continue;
+        };
+        let link = match &selectors.link {
+            Some(selector) => container.select(selector).next().and_then(href),
+            None => href(container),
+        }
+        .and_then(|href| base.join(href).ok())
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .map(String::from);
+        let pub_date = selectors.date.as_ref()
+            .and_then(|selector| container.select(selector).next())
+            .and_then(|element| {
+                let value = element.value();
+                value.attr("datetime").or(value.attr("content")).map(str::to_string).or_else(|| Some(text(element)))
+            })
+            .and_then(|date| dates::parse_date(&date));
+        let body = selectors.body.as_ref()
+            .and_then(|selector| container.select(selector).next())
+            .map(|element| sanitize::clean_html(&element.inner_html(), Some(page_url)));
+
+        // Items are keyed by link, or by title on pages that have none
+        let guid = link.clone().unwrap_or_else(|| item_title.clone());
+        if !seen.insert(guid.clone()) {
+            continue;
+        }
+        items.push(NewItem {
+            guid,
+            title: item_title,
+            thumbnail_url: body.as_deref().and_then(|body| media::first_image(body, Some(page_url))),
+            description: body,
+            link,
+            pub_date,
+            ..Default::default()
+        });
+    }
+
+    Ok(ParsedFeed {
+        title,
+        description: None,
+        items,
+        schedule: Default::default(),
+        websub: Default::default(),
+    })
+}
+
+fn text(element: ElementRef) -> String {
+    element.text().collect::<Vec<_>>().join(" ").split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The element's own `href`, or that of the first link inside it.
+fn href<'a>(element: ElementRef<'a>) -> Option<&'a str> {
+    element.value().attr("href").or_else(|| {
+        let links = Selector::parse("a[href]").ok()?;
+        element.select(&links).next()?.value().attr("href")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHANGELOG: &str = r#"<html><head><title> Vendor Changelog </title></head><body>
+        <div class="release"><h2>Version 2.1</h2><a href="/releases/2.1">Details</a>
+            <time datetime="2024-03-01T09:00:00Z">March 1st</time>
+            <div class="notes"><p>Fixed <b>bugs</b>.</p><script>alert(1)</script><img src="shot.png"></div></div>
+        <div class="release"><h2>Version 2.0</h2><span class="date">February 2, 2024</span></div>
+        <div class="release"><h2> </h2><a href="/releases/empty">No title</a></div>
+        <div class="release"><h2>Version 2.1</h2><a href="/releases/2.1">Repeated</a></div>
+    </body></html>"#;
+
+    fn rules(date_selector: &str) -> ScrapeRules {
+        ScrapeRules {
+            item_selector: "div.release".to_string(),
+            title_selector: "h2".to_string(),
+            link_selector: None,
+            date_selector: Some(date_selector.to_string()),
+            body_selector: Some(".notes".to_string()),
+        }
+    }
+
+    #[test]
+    fn scrapes_items() {
+        let feed = scrape_page(CHANGELOG, "https://vendor.example/changelog", &rules("time, .date")).unwrap();
+        assert_eq!(feed.title, "Vendor Changelog");
+        assert_eq!(feed.items.len(), 2);
+
+        let first = &feed.items[0];
+        assert_eq!(first.title, "Version 2.1");
+        assert_eq!(first.link.as_deref(), Some("https://vendor.example/releases/2.1"));
+        assert_eq!(first.guid, "https://vendor.example/releases/2.1");
+        assert_eq!(first.pub_date, dates::parse_date("2024-03-01T09:00:00Z"));
+        assert_eq!(
+            first.description.as_deref(),
+            Some(r#"<p>Fixed <b>bugs</b>.</p><img src="https://vendor.example/shot.png">"#)
+        );
+        assert_eq!(first.thumbnail_url.as_deref(), Some("https://vendor.example/shot.png"));
+
+        let second = &feed.items[1];
+        assert_eq!(second.guid, "Version 2.0");
+        assert_eq!(second.link, None);
+        assert_eq!(second.pub_date, dates::parse_date("2024-02-02"));
+        assert_eq!(second.description, None);
+    }
+
+    #[test]
+    fn checks_selectors() {
+        let mut tidy = check_rules(rules("  ")).unwrap();
+        assert_eq!(tidy.date_selector, None);
+
+        tidy.title_selector = "h2[".to_string();
+        assert!(check_rules(tidy.clone()).is_err());
+        tidy.title_selector = " ".to_string();
+        assert!(check_rules(tidy).is_err());
+    }
+}
@@ -1,5 +1,21 @@
 use maud::{html, Markup, DOCTYPE};
-use crate::models::{ItemWithReadStatus, Label, SubscriptionWithLabels};
+use crate::discovery::DiscoveredFeed;
+use crate::models::{
+    Enclosure, Feed, FeedHealth, FeedKind, Item, ItemWithReadStatus, Label, ScrapeRules,
+    SubscriptionWithLabels,
+};
+use crate::revisions::Edit;
+use similar::ChangeTag;
+
+/// Choices offered for a subscription's polling interval; "" means automatic.
+const REFRESH_INTERVAL_OPTIONS: [(&str, &str); 6] = [
+    ("", "Automatic"),
+    ("900", "Every 15 minutes"),
+    ("3600", "Every hour"),
+    ("21600", "Every 6 hours"),
+    ("43200", "Every 12 hours"),
+    ("86400", "Daily"),
+];
 
 pub fn base_layout(title: &str, username: Option<&str>, content: Markup) -> Markup {
     html! {
@@ -163,9 +179,18 @@ pub fn home_page(username: &str, items: &[ItemWithReadStatus], has_more: bool, p
             } @else {
                 @for item in items {
                     article class={"feed-item" @if item.is_read { " read" }} data-item-id=(item.item.id) {
+                        @if let Some(thumbnail) = item_thumbnail(item) {
+                            @if let Some(link) = item.item.link.as_deref().filter(|link| is_web_url(link)) {
+                                a href=(link) target="_blank" rel="noopener" {
+                                    img class="item-thumbnail" src=(thumbnail) alt="" loading="lazy";
+                                }
+                            } @else {
+                                img class="item-thumbnail" src=(thumbnail) alt="" loading="lazy";
+                            }
+                        }
                         div class="item-header" {
                             h3 class="item-title" {
-                                @if let Some(link) = &item.item.link {
+                                @if let Some(link) = item.item.link.as_deref().filter(|link| is_web_url(link)) {
                                     a href=(link) target="_blank" rel="noopener" { (item.item.title) }
                                 } @else {
                                     (item.item.title)
@@ -174,11 +199,16 @@ pub fn home_page(username: &str, items: &[ItemWithReadStatus], has_more: bool, p
                             div class="item-meta" {
                                 span class="feed-name" { (item.feed_title.as_deref().unwrap_or("Unknown Feed")) }
                                 @if let Some(pub_date) = item.item.pub_date {
-                                    span class="pub-date" { " • " (pub_date.format(&time::format_description::parse("[month repr:short] [day], [year]").unwrap()).unwrap_or_else(|_| "Unknown date".to_string())) }
+                                    span class="pub-date" { " • " (pub_date.format(time::macros::format_description!("[month repr:short] [day], [year]")).unwrap_or_else(|_| "Unknown date".to_string())) }
                                 }
                                 @if let Some(author) = &item.item.author {
                                     span class="author" { " • by " (author) }
                                 }
+                                @if item.item.revised_at.is_some() {
+                                    span class="item-updated" {
+                                        " • " a href={"/items/" (item.item.id) "/revisions"} { "Updated" }
+                                    }
+                                }
                             }
                         }
                         @if let Some(description) = &item.item.description {
@@ -186,6 +216,13 @@ pub fn home_page(username: &str, items: &[ItemWithReadStatus], has_more: bool, p
                                 (maud::PreEscaped(description))
                             }
                         }
+                        @if let Some(content) = item.item.content.as_ref().filter(|content| Some(*content) != item.item.description.as_ref()) {
+                            details class="item-content" {
+                                summary { "Full article" }
+                                (maud::PreEscaped(content))
+                            }
+                        }
+                        (episode_media(item))
                         @if !item.is_read {
                             button class="mark-read-btn" data-item-id=(item.item.id) { "Mark as Read" }
                         }
@@ -208,7 +245,142 @@ pub fn home_page(username: &str, items: &[ItemWithReadStatus], has_more: bool, p
     })
 }
 
-pub fn feeds_page(username: &str, subscriptions: &[SubscriptionWithLabels], labels: &[Label]) -> Markup {
+/// Preview image for the item list, unless the episode player already shows
+/// the item's artwork.
+fn item_thumbnail(item: &ItemWithReadStatus) -> Option<&str> {
+    let shows_artwork = item.enclosures.iter().any(|e| is_web_url(&e.url))
+        && item.item.image_url.as_deref().is_some_and(is_web_url);
+    if shows_artwork {
+        return None;
+    }
+    item.item.thumbnail_url.as_deref().filter(|url| is_web_url(url))
+}
+
+/// Player, artwork and downloads for an item's enclosures. The first audio
+/// or video file gets an inline player that resumes where the user left off.
+fn episode_media(item: &ItemWithReadStatus) -> Markup {
+    let media: Vec<&Enclosure> = item.enclosures.iter().filter(|e| is_web_url(&e.url)).collect();
+    let player = media.iter().copied().find(|e| e.media_kind().is_some());
+
+    let mut details = Vec::new();
+    if let Some(season) = item.item.season {
+        details.push(format!("Season {}", season));
+    }
+    if let Some(episode) = item.item.episode {
+        details.push(format!("Episode {}", episode));
+    }
+    if let Some(duration) = item.item.duration_seconds.or_else(|| player.and_then(|e| e.duration_seconds)) {
+        details.push(format_duration(duration));
+    }
+
+    html! {
+        @if !media.is_empty() {
+            div class="episode" {
+                @if let Some(image) = item.item.image_url.as_deref().filter(|url| is_web_url(url)) {
+                    img class="episode-image" src=(image) alt="" loading="lazy";
+                }
+                div class="episode-body" {
+                    @if !details.is_empty() {
+                        p class="episode-details" { (details.join(" · ")) }
+                    }
+                    @if let Some(enclosure) = player {
+                        @if enclosure.media_kind() == Some("video") {
+                            video class="episode-player" controls preload="none" src=(enclosure.url)
+                                data-item-id=(item.item.id) data-position=[item.playback_position] {}
+                        } @else {
+                            audio class="episode-player" controls preload="none" src=(enclosure.url)
+                                data-item-id=(item.item.id) data-position=[item.playback_position] {}
+                        }
+                    }
+                    ul class="enclosure-list" {
+                        @for enclosure in &media {
+                            li {
+                                a href=(enclosure.url) download { (enclosure.title.as_deref().unwrap_or("Download")) }
+                                @if let Some(mime_type) = &enclosure.mime_type {
+                                    " " span class="enclosure-meta" { (mime_type) }
+                                }
+                                @if let Some(length) = enclosure.length {
+                                    " " span class="enclosure-meta" { (format_size(length)) }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Feeds control enclosure and artwork URLs; only web URLs are linked so a
+/// `javascript:` one never ends up in an `href`.
+fn is_web_url(url: &str) -> bool {
+    let url = url.trim_start().to_ascii_lowercase();
+    url.starts_with("https://") || url.starts_with("http://")
+}
+
+fn format_duration(seconds: i64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+fn format_size(bytes: i64) -> String {
+    if bytes >= 1_000_000 {
+        format!("{:.1} MB", bytes as f64 / 1_000_000.0)
+    } else {
+        format!("{} KB", (bytes + 999) / 1000)
+    }
+}
+
+/// Every edit a feed has made to an item, newest first, as word diffs.
+pub fn item_revisions_page(username: &str, item: &Item, edits: &[Edit]) -> Markup {
+    let timestamp = time::macros::format_description!("[month repr:short] [day], [year] [hour]:[minute] UTC");
+    base_layout("Revisions", Some(username), html! {
+        div class="revisions-page" {
+            h2 {
+                @if let Some(link) = item.link.as_deref().filter(|link| is_web_url(link)) {
+                    a href=(link) target="_blank" rel="noopener" { (item.title) }
+                } @else {
+                    (item.title)
+                }
+            }
+            p { a href="/" { "Back to items" } }
+            @if edits.is_empty() {
+                p class="empty-state" { "This item has not been edited." }
+            }
+            @for edit in edits {
+                section class="revision" {
+                    h3 { "Edited " (edit.changed_at.format(timestamp).unwrap_or_default()) }
+                    @for (field, spans) in &edit.fields {
+                        h4 { (field) }
+                        p class="diff" {
+                            @for span in spans {
+                                @match span.tag {
+                                    ChangeTag::Delete => del { (span.text) },
+                                    ChangeTag::Insert => ins { (span.text) },
+                                    ChangeTag::Equal => (span.text),
+                                }
+                            }
+                        }
+                    }
+                    @if edit.fields.is_empty() {
+                        p class="form-hint" { "Only formatting changed." }
+                    }
+                }
+            }
+        }
+    })
+}
+
+pub fn feeds_page(
+    username: &str,
+    subscriptions: &[SubscriptionWithLabels],
+    labels: &[Label],
+    local_feeds: &[Feed],
+) -> Markup {
     base_layout("Manage Feeds", Some(username), html! {
         div class="feeds-page" {
             div class="add-feed-section" {
@@ -223,11 +395,72 @@ pub fn feeds_page(username: &str, subscriptions: &[SubscriptionWithLabels], labe
                         input type="text" id="labels" name="labels" placeholder="tech, news, personal";
                     }
                     div class="form-group" {
-                        label for="content" { "Or paste RSS/Atom content" }
+                        label for="content" { "Or paste RSS/Atom/JSON Feed content" }
                         textarea id="content" name="content" rows="6" {}
                     }
+                    details class="request-headers" {
+                        summary { "Request headers" }
+                        div class="form-group" {
+                            label for="headers" { "Headers, one per line" }
+                            textarea id="headers" name="headers" rows="3" placeholder="X-Api-Key: your-key" {}
+                        }
+                        div class="form-group" {
+                            label for="cookies" { "Cookies" }
+                            input type="text" id="cookies" name="cookies" placeholder="session=abc123";
+                        }
+                        div class="form-group" {
+                            label for="auth_username" { "HTTP Basic username" }
+                            input type="text" id="auth_username" name="auth_username" autocomplete="off";
+                        }
+                        div class="form-group" {
+                            label for="auth_password" { "HTTP Basic password" }
+                            input type="password" id="auth_password" name="auth_password" autocomplete="new-password";
+                        }
+                        p class="form-hint" { "For feeds that need an API key or a login. The feed will be private to you." }
+                    }
                     button type="submit" class="btn btn-primary" { "Add Feed" }
                 }
+                details class="scrape-feed" {
+                    summary { "Scrape a page without a feed" }
+                    form action="/feeds/scrape" method="post" class="add-feed-form" {
+                        div class="form-group" {
+                            label for="scrape-url" { "Page URL" }
+                            input type="url" id="scrape-url" name="url" placeholder="https://example.com/changelog" required;
+                        }
+                        (scrape_rule_fields("scrape", None))
+                        div class="form-group" {
+                            label for="scrape-headers" { "Headers, one per line" }
+                            textarea id="scrape-headers" name="headers" rows="2" placeholder="X-Api-Key: your-key" {}
+                        }
+                        div class="form-group" {
+                            label for="scrape-cookies" { "Cookies" }
+                            input type="text" id="scrape-cookies" name="cookies" placeholder="session=abc123";
+                        }
+                        p class="form-hint" { "The page is checked with these selectors before it is added. Scraped feeds are private to you." }
+                        button type="submit" class="btn btn-primary" { "Add Scraped Feed" }
+                    }
+                }
+            }
+            
+            @if !local_feeds.is_empty() {
+                div class="local-feeds-section" {
+                    h2 { "Local Feeds" }
+                    ul class="local-feed-list" {
+                        @for feed in local_feeds {
+                            li {
+                                span class="local-feed-title" { (feed.title.as_deref().unwrap_or(&feed.url)) }
+                                @if subscriptions.iter().any(|sub| sub.subscription.feed_id == feed.id) {
+                                    span class="local-feed-subscribed" { "Subscribed" }
+                                } @else {
+                                    form action="/feeds/add" method="post" class="inline-form" {
+                                        input type="hidden" name="url" value=(feed.url);
+                                        button type="submit" class="btn btn-sm" { "Subscribe" }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
             }
             
             div class="subscriptions-section" {
@@ -242,7 +475,12 @@ pub fn feeds_page(username: &str, subscriptions: &[SubscriptionWithLabels], labe
                                     h3 { 
                                         (sub.feed_title.as_deref().unwrap_or(&sub.feed_url))
                                     }
-                                    p class="feed-url" { (sub.feed_url) }
+                                    @if sub.feed_kind == FeedKind::Static {
+                                        p class="feed-url" { "Uploaded feed" }
+                                    } @else {
+                                        p class="feed-url" { (sub.feed_url) }
+                                        (feed_health(sub))
+                                    }
                                     div class="labels" {
                                         @for label in &sub.labels {
                                             span class="label" style={"background-color: " (label.color)} {
@@ -251,6 +489,31 @@ pub fn feeds_page(username: &str, subscriptions: &[SubscriptionWithLabels], labe
                                         }
                                         button class="edit-labels-btn" data-subscription-id=(sub.subscription.id) { "Edit Labels" }
                                     }
+                                    @if sub.feed_kind == FeedKind::Static {
+                                        (static_upload_form(sub))
+                                    } @else {
+                                        form action={"/feeds/" (sub.subscription.id) "/interval"} method="post" class="refresh-interval-form" {
+                                            label for={"interval-" (sub.subscription.id)} { "Refresh" }
+                                            select id={"interval-" (sub.subscription.id)} name="refresh_interval_secs" {
+                                                @for (secs, name) in REFRESH_INTERVAL_OPTIONS {
+                                                    option value=(secs) selected[sub.subscription.refresh_interval_secs == secs.parse().ok()] { (name) }
+                                                }
+                                            }
+                                            button type="submit" class="btn btn-sm" { "Save" }
+                                        }
+                                        @if let Some(rules) = &sub.scrape_rules {
+                                            (scrape_rules_form(sub, rules))
+                                        }
+                                        (request_headers_form(sub))
+                                        (credentials_form(sub))
+                                    }
+                                    form action={"/feeds/" (sub.subscription.id) "/full-content"} method="post" class="full-content-form" {
+                                        label {
+                                            input type="checkbox" name="fetch_full_content" value="on" checked[sub.subscription.fetch_full_content];
+                                            " Fetch full articles from item links"
+                                        }
+                                        button type="submit" class="btn btn-sm" { "Save" }
+                                    }
                                 }
                                 form action={"/feeds/" (sub.subscription.feed_id) "/unsubscribe"} method="post" class="inline-form" {
                                     button type="submit" class="btn btn-danger" 
@@ -292,6 +555,186 @@ pub fn feeds_page(username: &str, subscriptions: &[SubscriptionWithLabels], labe
     })
 }
 
+pub fn feed_chooser_page(username: &str, page_url: &str, feeds: &[DiscoveredFeed], labels: &[String]) -> Markup {
+    base_layout("Choose a Feed", Some(username), html! {
+        div class="feeds-page" {
+            div class="add-feed-section" {
+                h2 { "Choose a Feed" }
+                p class="chooser-intro" {
+                    (page_url) " is a web page that offers more than one feed. Pick the one to subscribe to."
+                }
+                div class="discovered-feeds" {
+                    @for feed in feeds {
+                        div class="discovered-feed" {
+                            div class="subscription-info" {
+                                h3 { (feed.title.as_deref().unwrap_or(&feed.url)) }
+                                p class="feed-url" {
+                                    (feed.url)
+                                    @if let Some(mime_type) = &feed.mime_type { " (" (mime_type) ")" }
+                                }
+                            }
+                            form action="/feeds/add" method="post" class="inline-form" {
+                                input type="hidden" name="url" value=(feed.url);
+                                @for label in labels {
+                                    input type="hidden" name="labels" value=(label);
+                                }
+                                button type="submit" class="btn btn-primary" { "Subscribe" }
+                            }
+                        }
+                    }
+                }
+                a href="/feeds" class="btn" { "Cancel" }
+            }
+        }
+    })
+}
+
+fn feed_health(sub: &SubscriptionWithLabels) -> Markup {
+    let status = &sub.fetch_status;
+    let (class, label) = match status.health() {
+        FeedHealth::Pending => ("pending", "Not fetched yet"),
+        FeedHealth::Healthy => ("healthy", "Healthy"),
+        FeedHealth::Degraded => ("degraded", "Recent fetches failing"),
+        FeedHealth::Failing => ("failing", "Failing"),
+        FeedHealth::Paused => ("paused", "Paused"),
+    };
+    let timestamp = time::macros::format_description!("[month repr:short] [day], [year] [hour]:[minute] UTC");
+
+    html! {
+        div class={"feed-health " (class)} {
+            span class="health-indicator" { (label) }
+            @if status.consecutive_failures > 0 {
+                span class="health-detail" {
+                    " • " (status.consecutive_failures) " failed "
+                    @if status.consecutive_failures == 1 { "attempt" } @else { "attempts" }
+                }
+            }
+            @if let Some(last_success_at) = status.last_success_at {
+                span class="health-detail" {
+                    " • last updated " (last_success_at.format(timestamp).unwrap_or_default())
+                }
+            }
+            @if let Some(error) = &status.last_error {
+                p class="health-error" { (error) }
+            }
+            @if status.paused_at.is_some() {
+                form action={"/feeds/" (sub.subscription.id) "/resume"} method="post" class="inline-form" {
+                    button type="submit" class="btn btn-sm" { "Resume" }
+                }
+            }
+        }
+    }
+}
+
+/// Custom headers and cookies for a subscription. Saved values are never
+/// rendered back; only the names of the headers in use are shown.
+fn request_headers_form(sub: &SubscriptionWithLabels) -> Markup {
+    let id = sub.subscription.id;
+    html! {
+        details class="request-headers" {
+            summary {
+                "Request headers"
+                @if !sub.request_header_names.is_empty() {
+                    span class="request-header-names" { " (" (sub.request_header_names.join(", ")) ")" }
+                }
+            }
+            form action={"/feeds/" (id) "/headers"} method="post" class="request-headers-form" {
+                div class="form-group" {
+                    label for={"headers-" (id)} { "Headers, one per line" }
+                    textarea id={"headers-" (id)} name="headers" rows="3" placeholder="X-Api-Key: your-key" {}
+                }
+                div class="form-group" {
+                    label for={"cookies-" (id)} { "Cookies" }
+                    input type="text" id={"cookies-" (id)} name="cookies" placeholder="session=abc123";
+                }
+                p class="form-hint" {
+                    "Saving replaces any headers set before; save an empty form to remove them. "
+                    "A feed fetched with custom headers is private to you."
+                }
+                button type="submit" class="btn btn-sm" { "Save" }
+            }
+        }
+    }
+}
+
+/// Selector inputs shared by the scraped feed form and the per-subscription
+/// editor; `prefix` keeps the element ids unique.
+fn scrape_rule_fields(prefix: &str, rules: Option<&ScrapeRules>) -> Markup {
+    let fields = [
+        ("item_selector", "Item selector", "article.entry", rules.map(|r| r.item_selector.as_str()), true),
+        ("title_selector", "Title selector", "h2", rules.map(|r| r.title_selector.as_str()), true),
+        ("link_selector", "Link selector (default: first link)", "a.permalink", rules.and_then(|r| r.link_selector.as_deref()), false),
+        ("date_selector", "Date selector", "time", rules.and_then(|r| r.date_selector.as_deref()), false),
+        ("body_selector", "Body selector", ".summary", rules.and_then(|r| r.body_selector.as_deref()), false),
+    ];
+    html! {
+        @for (name, label, placeholder, value, required) in fields {
+            div class="form-group" {
+                label for={(prefix) "-" (name)} { (label) }
+                input type="text" id={(prefix) "-" (name)} name=(name) placeholder=(placeholder) value=[value] required[required];
+            }
+        }
+    }
+}
+
+fn scrape_rules_form(sub: &SubscriptionWithLabels, rules: &ScrapeRules) -> Markup {
+    let id = sub.subscription.id;
+    html! {
+        details class="request-headers" {
+            summary { "Scrape rules" }
+            form action={"/feeds/" (id) "/scrape-rules"} method="post" class="request-headers-form" {
+                (scrape_rule_fields(&format!("rules-{}", id), Some(rules)))
+                p class="form-hint" { "Selectors are evaluated inside each item; the date may also come from a datetime attribute." }
+                button type="submit" class="btn btn-sm" { "Save" }
+            }
+        }
+    }
+}
+
+/// Re-upload form for a feed added by pasting its document.
+fn static_upload_form(sub: &SubscriptionWithLabels) -> Markup {
+    let id = sub.subscription.id;
+    html! {
+        details class="request-headers" {
+            summary { "Upload a newer version" }
+            form action={"/feeds/" (id) "/upload"} method="post" class="request-headers-form" {
+                div class="form-group" {
+                    label for={"upload-" (id)} { "RSS/Atom/JSON Feed content" }
+                    textarea id={"upload-" (id)} name="content" rows="6" required {}
+                }
+                p class="form-hint" { "New items are added; items already here are updated." }
+                button type="submit" class="btn btn-sm" { "Upload" }
+            }
+        }
+    }
+}
+
+/// HTTP Basic credentials for a subscription. Like request headers, stored
+/// credentials are never rendered back, only whether there are any.
+fn credentials_form(sub: &SubscriptionWithLabels) -> Markup {
+    let id = sub.subscription.id;
+    html! {
+        details class="request-headers" {
+            summary {
+                "HTTP Basic credentials"
+                @if sub.has_credentials { span class="request-header-names" { " (saved)" } }
+            }
+            form action={"/feeds/" (id) "/credentials"} method="post" class="request-headers-form" {
+                div class="form-group" {
+                    label for={"username-" (id)} { "Username" }
+                    input type="text" id={"username-" (id)} name="username" autocomplete="off";
+                }
+                div class="form-group" {
+                    label for={"password-" (id)} { "Password" }
+                    input type="password" id={"password-" (id)} name="password" autocomplete="new-password";
+                }
+                p class="form-hint" { "Saving replaces the stored credentials; save an empty username to remove them." }
+                button type="submit" class="btn btn-sm" { "Save" }
+            }
+        }
+    }
+}
+
 pub fn labels_page(username: &str, labels: &[Label]) -> Markup {
     base_layout("Manage Labels", Some(username), html! {
         div class="labels-page" {
@@ -0,0 +1,560 @@
+use crate::{
+    config::Config,
+    db,
+    encoding,
+    http_client::HttpClient,
+    models::{WebSubSubscription, WebSubVerification},
+    rss_fetcher::{self, ParsedFeed},
+};
+use anyhow::{Context, Result};
+use hmac::digest::KeyInit;
+use hmac::{Hmac, Mac};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use reqwest::header::{HeaderMap, LINK};
+use sha1::Sha1;
+use sha2::{Sha256, Sha384, Sha512};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use url::Url;
+use uuid::Uuid;
+
+/// Lease asked of hubs (10 days); they are free to grant another.
+const REQUESTED_LEASE_SECS: i32 = 864_000;
+/// Renew a lease this long before it runs out (1 day), or halfway through
+/// if it is shorter than two days.
+const RENEW_MARGIN_SECS: i64 = 86_400;
+/// Ask again if a hub has not verified a request within an hour.
+const VERIFY_TIMEOUT_SECS: i64 = 3_600;
+/// Ask again a day after a hub denied a subscription.
+const DENIED_RETRY_SECS: i64 = 86_400;
+
+/// WebSub discovery links: the hub a feed is published through, and the
+/// canonical feed URL (the topic) to subscribe to there.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WebSubLinks {
+    pub hub: Option<String>,
+    pub topic: Option<String>,
+}
+
+impl WebSubLinks {
+    /// Links from HTTP `Link` headers, e.g. `<https://hub.example/>; rel="hub"`.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let mut links = Self::default();
+        for value in headers.get_all(LINK).iter().filter_map(|v| v.to_str().ok()) {
+            for (url, rels) in parse_link_header(value) {
+                if links.hub.is_none() && rels.iter().any(|rel| rel.eq_ignore_ascii_case("hub")) {
+                    links.hub = Some(url.to_string());
+                }
+                if links.topic.is_none() && rels.iter().any(|rel| rel.eq_ignore_ascii_case("self")) {
+                    links.topic = Some(url.to_string());
+                }
+            }
+        }
+        links
+    }
+
+    /// Fill in whatever these links lack from `other`. The spec has links in
+    /// HTTP headers win over those in the document.
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            hub: self.hub.or(other.hub),
+            topic: self.topic.or(other.topic),
+        }
+    }
+}
+
+/// Split a `Link` header into its targets and their `rel` values.
+fn parse_link_header(value: &str) -> Vec<(&str, Vec<&str>)> {
+    let mut links = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>').map(|end| start + end) else {
+            break;
+        };
+        let url = rest[start + 1..end].trim();
+        rest = &rest[end + 1..];
+        let params = &rest[..rest.find('<').unwrap_or(rest.len())];
+
+        let rels = params
+            .split([';', ','])
+            .filter_map(|param| param.split_once('='))
+            .filter(|(name, _)| name.trim().eq_ignore_ascii_case("rel"))
+            .flat_map(|(_, value)| value.trim().trim_matches('"').split_whitespace())
+            .collect();
+        links.push((url, rels));
+    }
+    links
+}
+
+/// Where the hub should deliver a feed's updates, if the server has a
+/// public address to receive them at.
+fn callback_url(config: &Config, feed_id: Uuid) -> Option<String> {
+    config.public_url.as_ref().map(|base| format!("{}/websub/{}", base, feed_id))
+}
+
+fn new_secret() -> String {
+    rand::thread_rng().sample_iter(&Alphanumeric).take(40).map(char::from).collect()
+}
+
+/// Subscribe to the hub a freshly fetched feed advertises, unless an
+/// equivalent subscription is already active or awaiting verification.
+/// Only shared feeds are pushed; private copies are left to polling.
+pub async fn ensure_subscription(
+    pool: &PgPool,
+    client: &HttpClient,
+    config: &Config,
+    feed_id: Uuid,
+    feed_url: &str,
+    links: &WebSubLinks,
+) -> Result<()> {
+    let Some(callback) = callback_url(config, feed_id) else {
+        return Ok(());
+    };
+    let Some(hub) = &links.hub else {
+        // The feed no longer names a hub; let any lease we hold lapse
+        return db::delete_websub_subscription(pool, feed_id).await;
+    };
+    if !db::is_public_remote_feed(pool, feed_id).await? {
+        return Ok(());
+    }
+
+    let base = Url::parse(feed_url).with_context(|| format!("Invalid URL {}", feed_url))?;
+    let hub = base.join(hub).with_context(|| format!("Invalid hub URL {}", hub))?.to_string();
+    let topic = match &links.topic {
+        Some(topic) => base.join(topic).with_context(|| format!("Invalid topic URL {}", topic))?.to_string(),
+        None => feed_url.to_string(),
+    };
+
+    let existing = db::get_websub_subscription(pool, feed_id).await?
+        .filter(|existing| existing.hub_url == hub && existing.topic_url == topic);
+    if existing.as_ref().is_some_and(|existing| existing.renew_at > OffsetDateTime::now_utc()) {
+        return Ok(());
+    }
+
+    let secret = existing.map(|existing| existing.secret).unwrap_or_else(new_secret);
+    subscribe(pool, client, feed_id, &hub, &topic, &secret, &callback).await
+}
+
+/// Renew leases that are about to expire and retry requests that were never
+/// verified or were denied.
+pub async fn renew_due_subscriptions(pool: &PgPool, client: &HttpClient, config: &Config) -> Result<()> {
+    for subscription in db::get_due_websub_subscriptions(pool).await? {
+        let Some(callback) = callback_url(config, subscription.feed_id) else {
+            return Ok(());
+        };
+        let result = subscribe(
+            pool,
+            client,
+            subscription.feed_id,
+            &subscription.hub_url,
+            &subscription.topic_url,
+            &subscription.secret,
+            &callback,
+        )
+        .await;
+        if let Err(e) = result {
+            log::warn!("Failed to renew WebSub subscription to {} at {}: {:#}", subscription.topic_url, subscription.hub_url, e);
+        }
+    }
+    Ok(())
+}
+
+async fn subscribe(
+    pool: &PgPool,
+    client: &HttpClient,
+    feed_id: Uuid,
+    hub: &str,
+    topic: &str,
+    secret: &str,
+    callback: &str,
+) -> Result<()> {
+    // Stored first: hubs may verify the request before answering it
+    let retry_at = OffsetDateTime::now_utc() + time::Duration::seconds(VERIFY_TIMEOUT_SECS);
+    db::request_websub_subscription(pool, feed_id, hub, topic, secret, retry_at).await?;
+
+    log::info!("Subscribing to {} at WebSub hub {}", topic, hub);
+    send_subscription_request(client, hub, topic, secret, callback).await
+}
+
+/// Ask a hub to push `topic` to `callback`, signing each delivery with
+/// `secret`. A hub accepts with 202 and verifies the intent separately.
+pub async fn send_subscription_request(
+    client: &HttpClient,
+    hub: &str,
+    topic: &str,
+    secret: &str,
+    callback: &str,
+) -> Result<()> {
+    let lease = REQUESTED_LEASE_SECS.to_string();
+    let form = [
+        ("hub.mode", "subscribe"),
+        ("hub.callback", callback),
+        ("hub.topic", topic),
+        ("hub.secret", secret),
+        ("hub.lease_seconds", lease.as_str()),
+    ];
+    let response = client.post_form(hub, &form).await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("Hub {} refused the subscription ({}): {}", hub, status, body.chars().take(200).collect::<String>());
+    }
+    Ok(())
+}
+
+/// What a hub's verification of intent comes down to, given the feed's
+/// stored subscription.
+#[derive(Debug, PartialEq, Eq)]
+enum Intent {
+    /// Confirm the subscription we asked for, leased for `lease` seconds
+    Subscribe { challenge: String, lease: i32 },
+    /// The hub turned down the subscription we asked for
+    Denied,
+    /// Answer with this body and change nothing
+    Reply(String),
+    /// Not something we asked for; answer 404
+    Refuse,
+}
+
+fn read_intent(subscription: Option<&WebSubSubscription>, verification: &WebSubVerification) -> Intent {
+    let wanted = subscription.is_some_and(|subscription| subscription.topic_url == verification.topic);
+
+    match (verification.mode.as_str(), &verification.challenge) {
+        ("subscribe", Some(challenge)) if wanted => Intent::Subscribe {
+            challenge: challenge.clone(),
+            lease: verification.lease_seconds.unwrap_or(REQUESTED_LEASE_SECS).max(1),
+        },
+        // Only agree to stop deliveries we no longer want
+        ("unsubscribe", Some(challenge)) if !wanted => Intent::Reply(challenge.clone()),
+        ("denied", _) if wanted => Intent::Denied,
+        ("denied", _) => Intent::Reply(String::new()),
+        _ => Intent::Refuse,
+    }
+}
+
+/// Answer a hub's verification of intent. Returns what to reply with, the
+/// challenge when confirming, or `None` for a 404 when we do not want what
+/// the hub is asking about.
+pub async fn verify_intent(pool: &PgPool, feed_id: Uuid, verification: &WebSubVerification) -> Result<Option<String>> {
+    let subscription = db::get_websub_subscription(pool, feed_id).await?;
+    let now = OffsetDateTime::now_utc();
+
+    match read_intent(subscription.as_ref(), verification) {
+        Intent::Subscribe { challenge, lease } => {
+            let expires_at = now + time::Duration::seconds(lease.into());
+            let margin = RENEW_MARGIN_SECS.min(i64::from(lease) / 2);
+            db::activate_websub_subscription(pool, feed_id, lease, expires_at, expires_at - time::Duration::seconds(margin)).await?;
+            log::info!("WebSub subscription to {} verified for {} seconds", verification.topic, lease);
+            Ok(Some(challenge))
+        }
+        Intent::Denied => {
+            log::warn!(
+                "WebSub hub denied subscription to {}: {}",
+                verification.topic,
+                verification.reason.as_deref().unwrap_or("no reason given")
+            );
+            db::deny_websub_subscription(pool, feed_id, now + time::Duration::seconds(DENIED_RETRY_SECS)).await?;
+            Ok(Some(String::new()))
+        }
+        Intent::Reply(reply) => Ok(Some(reply)),
+        Intent::Refuse => Ok(None),
+    }
+}
+
+/// Check a pushed payload's `X-Hub-Signature` (`sha256=<hex>` and the like)
+/// against the subscription secret.
+pub fn verify_signature(secret: &str, signature: &str, body: &[u8]) -> bool {
+    let Some((method, digest)) = signature.trim().split_once('=') else {
+        return false;
+    };
+    let Ok(digest) = hex::decode(digest) else {
+        return false;
+    };
+    match method.to_ascii_lowercase().as_str() {
+        "sha1" => verify_mac::<Hmac<Sha1>>(secret, body, &digest),
+        "sha256" => verify_mac::<Hmac<Sha256>>(secret, body, &digest),
+        "sha384" => verify_mac::<Hmac<Sha384>>(secret, body, &digest),
+        "sha512" => verify_mac::<Hmac<Sha512>>(secret, body, &digest),
+        _ => false,
+    }
+}
+
+fn verify_mac<M: Mac + KeyInit>(secret: &str, body: &[u8], digest: &[u8]) -> bool {
+    let Ok(mut mac) = <M as KeyInit>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(digest).is_ok()
+}
+
+/// A payload a hub delivered to a feed's callback.
+#[derive(Debug)]
+enum Delivery {
+    /// There is no subscription for the feed
+    Unknown,
+    /// The signature is missing or wrong
+    Forged,
+    /// Signed with the subscription secret; the feed it carries
+    Genuine(ParsedFeed),
+}
+
+fn open_delivery(
+    subscription: Option<&WebSubSubscription>,
+    signature: Option<&str>,
+    content_type: Option<&str>,
+    body: &[u8],
+) -> Result<Delivery> {
+    let Some(subscription) = subscription else {
+        return Ok(Delivery::Unknown);
+    };
+    if !signature.is_some_and(|signature| verify_signature(&subscription.secret, signature, body)) {
+        return Ok(Delivery::Forged);
+    }
+
+    let content = encoding::decode_to_utf8(body, content_type);
+    let parsed = rss_fetcher::parse_feed(&content)
+        .with_context(|| format!("Failed to parse WebSub delivery for {}", subscription.topic_url))?;
+    Ok(Delivery::Genuine(parsed))
+}
+
+/// Store content a hub pushed for a feed. Returns false when there is no
+/// subscription for the feed. A payload with a missing or wrong signature
+/// is dropped but still acknowledged, as the spec requires, so a forger
+/// learns nothing.
+pub async fn receive_push(
+    pool: &PgPool,
+    feed_id: Uuid,
+    signature: Option<&str>,
+    content_type: Option<&str>,
+    body: &[u8],
+) -> Result<bool> {
+    let subscription = db::get_websub_subscription(pool, feed_id).await?;
+
+    match open_delivery(subscription.as_ref(), signature, content_type, body)? {
+        Delivery::Unknown => Ok(false),
+        Delivery::Forged => {
+            log::warn!("Ignoring WebSub delivery for feed {} with a bad signature", feed_id);
+            Ok(true)
+        }
+        Delivery::Genuine(parsed) => {
+            rss_fetcher::update_feed_items(pool, feed_id, &parsed).await?;
+            db::record_websub_push(pool, feed_id).await?;
+            log::debug!("Stored WebSub delivery for feed {}", feed_id);
+            Ok(true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    fn sign<M: Mac + KeyInit>(secret: &str, body: &[u8]) -> String {
+        let mut mac = <M as KeyInit>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    #[test]
+    fn accepts_valid_signatures() {
+        let body = b"<feed/>";
+        assert!(verify_signature("s3cret", &format!("sha1={}", sign::<Hmac<Sha1>>("s3cret", body)), body));
+        assert!(verify_signature("s3cret", &format!("sha256={}", sign::<Hmac<Sha256>>("s3cret", body)), body));
+        assert!(verify_signature("s3cret", &format!("SHA512={}", sign::<Hmac<Sha512>>("s3cret", body)), body));
+    }
+
+    #[test]
+    fn rejects_bad_signatures() {
+        let body = b"<feed/>";
+        let signature = format!("sha256={}", sign::<Hmac<Sha256>>("s3cret", body));
+        assert!(!verify_signature("other", &signature, body));
+        assert!(!verify_signature("s3cret", &signature, b"<feed>tampered</feed>"));
+        assert!(!verify_signature("s3cret", "sha256=not-hex", body));
+        assert!(!verify_signature("s3cret", "md5=00", body));
+        assert!(!verify_signature("s3cret", "", body));
+    }
+
+    #[test]
+    fn reads_link_headers() {
+        let mut headers = HeaderMap::new();
+        headers.append(LINK, r#"<https://hub.example/>; rel="hub", <https://example.com/feed.xml>; rel=self"#.parse().unwrap());
+        assert_eq!(
+            WebSubLinks::from_headers(&headers),
+            WebSubLinks {
+                hub: Some("https://hub.example/".to_string()),
+                topic: Some("https://example.com/feed.xml".to_string()),
+            }
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.append(LINK, r#"<https://example.com/a,b>; rel="self alternate""#.parse().unwrap());
+        let links = WebSubLinks::from_headers(&headers);
+        assert_eq!(links.hub, None);
+        assert_eq!(links.topic.as_deref(), Some("https://example.com/a,b"));
+    }
+
+    #[test]
+    fn discovers_hubs_in_documents() {
+        let atom = br#"<feed xmlns="http://www.w3.org/2005/Atom"><title>T</title><id>t</id><updated>2024-01-01T00:00:00Z</updated>
+            <link rel="hub" href="https://hub.example/"/><link rel="self" href="https://example.com/atom.xml"/></feed>"#;
+        let parsed = rss_fetcher::parse_feed(atom).unwrap();
+        assert_eq!(parsed.websub.hub.as_deref(), Some("https://hub.example/"));
+        assert_eq!(parsed.websub.topic.as_deref(), Some("https://example.com/atom.xml"));
+
+        let rss = br#"<rss version="2.0" xmlns:a="http://www.w3.org/2005/Atom"><channel><title>T</title><link>https://example.com/</link><description>D</description>
+            <a:link rel="hub" href="https://hub.example/"/><a:link rel="self" href="https://example.com/rss.xml"/></channel></rss>"#;
+        let parsed = rss_fetcher::parse_feed(rss).unwrap();
+        assert_eq!(parsed.websub.hub.as_deref(), Some("https://hub.example/"));
+        assert_eq!(parsed.websub.topic.as_deref(), Some("https://example.com/rss.xml"));
+
+        let json = br#"{"version": "https://jsonfeed.org/version/1.1", "title": "T", "feed_url": "https://example.com/feed.json",
+            "hubs": [{"type": "rssCloud", "url": "https://cloud.example/"}, {"type": "WebSub", "url": "https://hub.example/"}], "items": []}"#;
+        let parsed = rss_fetcher::parse_feed(json).unwrap();
+        assert_eq!(parsed.websub.hub.as_deref(), Some("https://hub.example/"));
+        assert_eq!(parsed.websub.topic.as_deref(), Some("https://example.com/feed.json"));
+    }
+
+    /// A stand-in hub that accepts and records subscription requests.
+    #[actix_web::test]
+    async fn subscribes_through_stand_in_hub() {
+        let requests: Arc<Mutex<Vec<HashMap<String, String>>>> = Arc::default();
+        let recorded = requests.clone();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let hub_url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = HttpServer::new(move || {
+            let recorded = recorded.clone();
+            App::new().route("/", web::post().to(move |form: web::Form<HashMap<String, String>>| {
+                let recorded = recorded.clone();
+                async move {
+                    recorded.lock().unwrap().push(form.into_inner());
+                    HttpResponse::Accepted().finish()
+                }
+            }))
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let client = HttpClient::new(&Config::for_tests()).unwrap();
+        send_subscription_request(&client, &hub_url, "https://example.com/feed.xml", "s3cret", "https://rss.example/websub/1")
+            .await
+            .unwrap();
+
+        let request = requests.lock().unwrap().pop().expect("hub received no request");
+        assert_eq!(request["hub.mode"], "subscribe");
+        assert_eq!(request["hub.topic"], "https://example.com/feed.xml");
+        assert_eq!(request["hub.callback"], "https://rss.example/websub/1");
+        assert_eq!(request["hub.lease_seconds"], REQUESTED_LEASE_SECS.to_string());
+
+        // Deliveries are signed with the secret handed to the hub
+        let body = b"<rss version=\"2.0\"><channel><title>T</title></channel></rss>";
+        let signature = format!("sha1={}", sign::<Hmac<Sha1>>(&request["hub.secret"], body));
+        assert!(verify_signature("s3cret", &signature, body));
+
+        let refused = send_subscription_request(&client, &format!("{}missing", hub_url), "t", "s", "c").await;
+        assert!(refused.is_err());
+
+        handle.stop(true).await;
+    }
+
+    fn subscription(topic: &str) -> WebSubSubscription {
+        WebSubSubscription {
+            feed_id: Uuid::nil(),
+            hub_url: "https://hub.example/".to_string(),
+            topic_url: topic.to_string(),
+            secret: "s3cret".to_string(),
+            renew_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    fn verification(mode: &str, topic: &str) -> WebSubVerification {
+        WebSubVerification {
+            mode: mode.to_string(),
+            topic: topic.to_string(),
+            challenge: Some("c4allenge".to_string()),
+            lease_seconds: Some(600),
+            reason: None,
+        }
+    }
+
+    #[test]
+    fn confirms_only_subscriptions_we_asked_for() {
+        let topic = "https://example.com/feed.xml";
+        let ours = subscription(topic);
+        assert_eq!(
+            read_intent(Some(&ours), &verification("subscribe", topic)),
+            Intent::Subscribe { challenge: "c4allenge".to_string(), lease: 600 }
+        );
+
+        // A topic other than the one we subscribed to
+        let other = verification("subscribe", "https://example.com/other.xml");
+        assert_eq!(read_intent(Some(&ours), &other), Intent::Refuse);
+        // A feed we hold no subscription for
+        assert_eq!(read_intent(None, &verification("subscribe", topic)), Intent::Refuse);
+        // No challenge to echo
+        let unchallenged = WebSubVerification { challenge: None, ..verification("subscribe", topic) };
+        assert_eq!(read_intent(Some(&ours), &unchallenged), Intent::Refuse);
+    }
+
+    #[test]
+    fn confirms_unsubscribing_only_what_we_dropped() {
+        let topic = "https://example.com/feed.xml";
+        let ours = subscription(topic);
+        assert_eq!(read_intent(Some(&ours), &verification("unsubscribe", topic)), Intent::Refuse);
+        assert_eq!(
+            read_intent(None, &verification("unsubscribe", topic)),
+            Intent::Reply("c4allenge".to_string())
+        );
+        assert_eq!(
+            read_intent(Some(&ours), &verification("unsubscribe", "https://example.com/other.xml")),
+            Intent::Reply("c4allenge".to_string())
+        );
+    }
+
+    #[test]
+    fn records_denials_only_for_our_subscription() {
+        let topic = "https://example.com/feed.xml";
+        let ours = subscription(topic);
+        assert_eq!(read_intent(Some(&ours), &verification("denied", topic)), Intent::Denied);
+        assert_eq!(
+            read_intent(Some(&ours), &verification("denied", "https://example.com/other.xml")),
+            Intent::Reply(String::new())
+        );
+        assert_eq!(read_intent(None, &verification("denied", topic)), Intent::Reply(String::new()));
+        assert_eq!(read_intent(Some(&ours), &verification("publish", topic)), Intent::Refuse);
+    }
+
+    #[test]
+    fn stores_only_signed_deliveries() {
+        let ours = subscription("https://example.com/feed.xml");
+        let body = br#"<rss version="2.0"><channel><title>T</title><link>https://example.com/</link><description>D</description>
+            <item><title>Pushed</title><guid>1</guid></item></channel></rss>"#;
+        let signature = format!("sha256={}", sign::<Hmac<Sha256>>("s3cret", body));
+        let open = |subscription, signature| open_delivery(subscription, signature, Some("application/rss+xml"), body).unwrap();
+
+        match open(Some(&ours), Some(signature.as_str())) {
+            Delivery::Genuine(parsed) => {
+                assert_eq!(parsed.items.len(), 1);
+                assert_eq!(parsed.items[0].title, "Pushed");
+            }
+            other => panic!("expected a genuine delivery, got {:?}", other),
+        }
+
+        let forged = format!("sha256={}", sign::<Hmac<Sha256>>("guess", body));
+        assert!(matches!(open(Some(&ours), Some(forged.as_str())), Delivery::Forged));
+        assert!(matches!(open(Some(&ours), None), Delivery::Forged));
+        assert!(matches!(open(None, Some(signature.as_str())), Delivery::Unknown));
+
+        // A genuine delivery that is not a feed is an error, not silently dropped
+        let garbage = b"not a feed";
+        let signature = format!("sha256={}", sign::<Hmac<Sha256>>("s3cret", garbage));
+        assert!(open_delivery(Some(&ours), Some(&signature), None, garbage).is_err());
+    }
+}
@@ -27,6 +27,49 @@ document.addEventListener('DOMContentLoaded', function() {
         });
     });
     
+    // Resume podcast and video players where the user left off, and keep
+    // saving their position as they listen
+    document.querySelectorAll('.episode-player').forEach(player => {
+        const itemId = player.dataset.itemId;
+        let lastSaved = 0;
+        
+        const savePosition = (position) => {
+            lastSaved = Date.now();
+            fetch(`/api/items/${itemId}/position`, {
+                method: 'POST',
+                headers: {
+                    'Content-Type': 'application/json',
+                },
+                body: JSON.stringify({
+                    position_seconds: position
+                }),
+                keepalive: true
+            }).catch(error => console.error('Failed to save playback position:', error));
+        };
+        
+        player.addEventListener('loadedmetadata', function() {
+            const position = parseFloat(this.dataset.position);
+            if (position > 0 && position < this.duration - 5) {
+                this.currentTime = position;
+            }
+        });
+        
+        player.addEventListener('timeupdate', function() {
+            if (!this.paused && Date.now() - lastSaved > 15000) {
+                savePosition(this.currentTime);
+            }
+        });
+        
+        player.addEventListener('pause', function() {
+            if (!this.ended) {
+                savePosition(this.currentTime);
+            }
+        });
+        
+        // Start over next time
+        player.addEventListener('ended', () => savePosition(0));
+    });
+    
     // Handle edit labels buttons
     document.querySelectorAll('.edit-labels-btn').forEach(button => {
         button.addEventListener('click', function() {